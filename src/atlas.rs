@@ -0,0 +1,62 @@
+use std::{collections::HashMap, io::Read};
+
+use ggez::graphics::Rect;
+use serde::Deserialize;
+
+/// 清单文件的内容：贴图路径（相对于清单所在目录，`/` 开头时为资源目录中的绝对路径）
+/// 和每一帧的像素矩形 `[x, y, 宽, 高]`
+/// ```toml
+/// image = "atlas.png"
+///
+/// [frames]
+/// wall = [0, 0, 32, 32]
+/// player_1 = [0, 64, 32, 32]
+/// ```
+#[derive(Deserialize)]
+struct AtlasFile {
+    image: String,
+    #[serde(default)]
+    frames: HashMap<String, [u32; 4]>,
+}
+
+/// 精灵图集：一张贴图，以及 `Renderable` 中的帧名在贴图中的位置
+pub struct Atlas {
+    /// 贴图在资源目录中的路径
+    pub image: String,
+    frames: HashMap<String, Rect>,
+}
+
+impl Atlas {
+    /// 读取图集清单，没有清单文件时返回 None（所有帧都单独加载）
+    /// 清单损坏时只打印提示，同样返回 None
+    pub fn load(ctx: &ggez::Context, path: &str) -> Option<Atlas> {
+        let mut text = String::new();
+        ctx.fs.open(path).ok()?.read_to_string(&mut text).ok()?;
+        let file: AtlasFile = match toml::from_str(&text) {
+            Ok(file) => file,
+            Err(error) => {
+                println!("ignoring corrupt atlas manifest {}: {}", path, error);
+                return None;
+            }
+        };
+
+        let mut frames = HashMap::new();
+        for (name, [x, y, w, h]) in file.frames {
+            if w == 0 || h == 0 {
+                println!("ignoring empty atlas frame '{}'", name);
+                continue;
+            }
+            frames.insert(name, Rect::new(x as f32, y as f32, w as f32, h as f32));
+        }
+        let image = match (file.image.starts_with('/'), path.rsplit_once('/')) {
+            (false, Some((dir, _))) => format!("{}/{}", dir, file.image),
+            _ => file.image,
+        };
+        Some(Atlas { image, frames })
+    }
+
+    /// 帧在贴图中的像素矩形，图集中没有该帧时返回 None
+    pub fn frame(&self, name: &str) -> Option<Rect> {
+        self.frames.get(name).copied()
+    }
+}
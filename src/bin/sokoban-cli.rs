@@ -0,0 +1,296 @@
+//! 推箱子命令行工具：校验、求解和转换关卡包，不打开游戏窗口，可以在脚本中使用
+
+use std::{env, fs, io::{self, ErrorKind, Write}, path::Path, process::ExitCode, time::{Duration, Instant}};
+
+use hecs::World;
+use rust_sokoban::{
+    component::{GamePlay, Ruleset},
+    entity,
+    level_pack::{LevelLibrary, LevelPack},
+    map::{MapError, export_map, export_xsb_map, load_level_map},
+    solver::{SolverLimits, solve_world},
+};
+
+const USAGE: &str = "\
+Usage:
+  sokoban-cli validate <FILE>... [--level N]
+  sokoban-cli solve <FILE>... [--level N] [--ruleset classic|multipush] [--max-nodes N] [--max-time SECONDS]
+  sokoban-cli convert <FILE> --to internal|xsb [--output FILE]
+
+Levels are numbered from 1 within each pack. `--ruleset` is used for levels
+whose pack does not specify one. The exit code is 1 when a level is invalid,
+unsolved or cannot be converted, and 2 on usage errors.";
+
+/// 转换的目标格式
+#[derive(Clone, Copy)]
+enum Format {
+    Internal,
+    Xsb,
+}
+
+/// 子命令失败的原因
+enum CommandError {
+    /// 参数或输入文件有误，打印错误和用法
+    Usage(String),
+    /// 写标准输出失败；管道另一端已经关闭（例如 `| head`）时安静地退出
+    Output(io::Error),
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        CommandError::Usage(message)
+    }
+}
+
+impl From<io::Error> for CommandError {
+    fn from(error: io::Error) -> Self {
+        CommandError::Output(error)
+    }
+}
+
+/// 命令行选项，各个子命令只使用自己需要的部分
+struct Options {
+    files: Vec<String>,
+    level: Option<usize>,
+    ruleset: Ruleset,
+    limits: SolverLimits,
+    format: Option<Format>,
+    output: Option<String>,
+}
+
+impl Options {
+    /// 解析子命令之后的参数
+    fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options {
+            files: Vec::new(),
+            level: None,
+            ruleset: Ruleset::default(),
+            limits: SolverLimits::default(),
+            format: None,
+            output: None,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                options.files.push(arg.clone());
+                continue;
+            }
+            let value = args.next().ok_or_else(|| format!("missing value for {}", arg))?;
+            match arg.as_str() {
+                "--level" => {
+                    options.level = Some(value.parse().ok().filter(|&level| level > 0).ok_or_else(|| invalid(arg, value))?)
+                }
+                "--ruleset" => options.ruleset = Ruleset::parse(value).ok_or_else(|| invalid(arg, value))?,
+                "--max-nodes" => options.limits.max_nodes = value.parse().map_err(|_| invalid(arg, value))?,
+                "--max-time" => {
+                    let seconds: f64 = value.parse().ok().filter(|seconds: &f64| *seconds > 0.0).ok_or_else(|| invalid(arg, value))?;
+                    options.limits.max_time = Duration::from_secs_f64(seconds);
+                }
+                "--to" => {
+                    options.format = Some(match value.to_ascii_lowercase().as_str() {
+                        "internal" => Format::Internal,
+                        "xsb" => Format::Xsb,
+                        _ => return Err(invalid(arg, value)),
+                    })
+                }
+                "--output" => options.output = Some(value.clone()),
+                _ => return Err(format!("unknown option {}", arg)),
+            }
+        }
+
+        if options.files.is_empty() {
+            return Err("no level file given".to_string());
+        }
+        Ok(options)
+    }
+}
+
+/// 选项值无效时的错误信息
+fn invalid(option: &str, value: &str) -> String {
+    format!("invalid value '{}' for {}", value, option)
+}
+
+/// 读取关卡包文件，以文件名（不含扩展名）作为默认标题
+fn read_pack(path: &str) -> Result<LevelLibrary, String> {
+    let text = fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+    let default_title = Path::new(path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let library = LevelLibrary::from_packs(vec![LevelPack::parse(&default_title, &text)]);
+    if library.is_empty() {
+        return Err(format!("{}: no levels found", path));
+    }
+    Ok(library)
+}
+
+/// 要处理的关卡编号：指定了 `--level` 时只处理该关卡
+fn selected_levels(library: &LevelLibrary, options: &Options, path: &str) -> Result<Vec<usize>, String> {
+    match options.level {
+        Some(level) if level <= library.len() => Ok(vec![level - 1]),
+        Some(level) => Err(format!("{}: level {} not found, the pack has {} levels", path, level, library.len())),
+        None => Ok((0..library.len()).collect()),
+    }
+}
+
+/// 把关卡加载到一个只包含地图和规则的世界中（不需要 ggez 上下文）
+/// 同时返回加载结果，关卡不合法时世界中仍保留解析出的地图
+fn load_world(library: &LevelLibrary, level_index: usize, ruleset: Ruleset) -> (World, Result<(), MapError>) {
+    let mut world = World::new();
+    let result = match library.get(level_index) {
+        Some((_, level)) => load_level_map(&mut world, level.map.clone()),
+        None => Ok(()),
+    };
+    entity::create_ruleset(&mut world, library.ruleset(level_index, ruleset));
+    (world, result)
+}
+
+/// 输出中标识关卡的前缀，例如 `classic.txt #3 Corridor`
+fn label(path: &str, library: &LevelLibrary, level_index: usize) -> String {
+    format!("{} #{} {}", path, level_index + 1, library.display_name(level_index))
+}
+
+/// 校验关卡，返回是否全部有效
+fn validate(options: &Options) -> Result<bool, CommandError> {
+    let mut out = io::stdout().lock();
+    let mut all_valid = true;
+    for path in options.files.iter() {
+        let library = read_pack(path)?;
+        for level_index in selected_levels(&library, options, path)? {
+            let (_, result) = load_world(&library, level_index, options.ruleset);
+            match result {
+                Ok(()) => writeln!(out, "{}: ok", label(path, &library, level_index))?,
+                Err(error) => {
+                    for message in error.messages() {
+                        writeln!(out, "{}: {}", label(path, &library, level_index), message)?;
+                    }
+                    all_valid = false;
+                }
+            }
+        }
+    }
+    Ok(all_valid)
+}
+
+/// 求解关卡并输出统计信息和 LURD 解法，返回是否全部解出
+fn solve(options: &Options) -> Result<bool, CommandError> {
+    let mut out = io::stdout().lock();
+    let mut all_solved = true;
+    for path in options.files.iter() {
+        let library = read_pack(path)?;
+        for level_index in selected_levels(&library, options, path)? {
+            let label = label(path, &library, level_index);
+            let (world, result) = load_world(&library, level_index, options.ruleset);
+            if let Err(error) = result {
+                writeln!(out, "{}: invalid level: {}", label, error)?;
+                all_solved = false;
+                continue;
+            }
+
+            let start = Instant::now();
+            let result = solve_world(&world, &options.limits);
+            let elapsed = start.elapsed().as_secs_f64();
+            match result {
+                Ok(solution) => {
+                    // 与游戏中相同的方式统计步数、推动次数和直线段数
+                    let mut gameplay = GamePlay::default();
+                    let lurd = solution.lurd();
+                    for step in lurd.chars() {
+                        gameplay.record_step(step);
+                    }
+                    writeln!(
+                        out,
+                        "{}: solved, {} moves, {} pushes, {} box lines, {} player lines ({} nodes, {:.2}s)",
+                        label, gameplay.move_count, gameplay.push_count, gameplay.box_lines, gameplay.player_lines, solution.nodes, elapsed
+                    )?;
+                    writeln!(out, "{}", lurd)?;
+                }
+                Err(error) => {
+                    writeln!(out, "{}: {} ({:.2}s)", label, error, elapsed)?;
+                    all_solved = false;
+                }
+            }
+        }
+    }
+    Ok(all_solved)
+}
+
+/// 把关卡包转换为指定格式，元信息保持不变
+/// 不合法的关卡照常转换，任意关卡无法解析或无法转换时不写出任何内容
+fn convert(options: &Options) -> Result<bool, CommandError> {
+    let format = options.format.ok_or_else(|| "convert requires --to internal|xsb".to_string())?;
+    let [path] = options.files.as_slice() else {
+        return Err("convert takes exactly one level file".to_string().into());
+    };
+    let mut library = read_pack(path)?;
+
+    let mut maps = Vec::with_capacity(library.len());
+    let mut all_converted = true;
+    for level_index in 0..library.len() {
+        let (world, result) = load_world(&library, level_index, options.ruleset);
+        if let Err(error @ (MapError::UnknownToken { .. } | MapError::TooLarge { .. })) = result {
+            eprintln!("{}: {}", label(path, &library, level_index), error);
+            all_converted = false;
+            continue;
+        }
+        let result = match format {
+            Format::Internal => export_map(&world).map_err(|error| error.to_string()),
+            Format::Xsb => export_xsb_map(&world).map_err(|error| error.to_string()),
+        };
+        match result {
+            Ok(map) => maps.push(map),
+            Err(error) => {
+                eprintln!("{}: {}", label(path, &library, level_index), error);
+                all_converted = false;
+            }
+        }
+    }
+    if !all_converted {
+        return Ok(false);
+    }
+
+    let pack = &mut library.packs[0];
+    for (level, map) in pack.levels.iter_mut().zip(maps) {
+        level.map = map;
+    }
+    let text = pack.to_text();
+    match options.output.as_ref() {
+        Some(output) => fs::write(output, text).map_err(|error| format!("{}: {}", output, error))?,
+        None => io::stdout().lock().write_all(text.as_bytes())?,
+    }
+    Ok(true)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some((command, args)) = args.split_first() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+    if matches!(command.as_str(), "help" | "-h" | "--help") {
+        // 输出被关闭时没有什么可做的，直接退出
+        let _ = writeln!(io::stdout().lock(), "{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    let result = Options::parse(args).map_err(CommandError::Usage).and_then(|options| match command.as_str() {
+        "validate" => validate(&options),
+        "solve" => solve(&options),
+        "convert" => convert(&options),
+        _ => Err(CommandError::Usage(format!("unknown command '{}'", command))),
+    });
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(CommandError::Usage(error)) => {
+            eprintln!("error: {}\n\n{}", error, USAGE);
+            ExitCode::from(2)
+        }
+        Err(CommandError::Output(error)) if error.kind() == ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(CommandError::Output(error)) => {
+            eprintln!("error: {}", error);
+            ExitCode::from(2)
+        }
+    }
+}
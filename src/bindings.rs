@@ -0,0 +1,426 @@
+use std::{collections::{BTreeMap, HashMap, HashSet}, fs, path::PathBuf, time::Duration};
+
+use ggez::{event::{Axis, Button}, input::keyboard::KeyCode};
+use serde::Deserialize;
+
+use crate::grid::Direction;
+
+/// 按键配置文件名（位于用户数据目录下）
+pub const BINDINGS_FILE: &str = "bindings.toml";
+/// 摇杆偏移超过该值时视为按下对应方向
+const STICK_THRESHOLD: f32 = 0.5;
+
+/// 可以绑定按键的动作
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Undo,
+    Redo,
+    Restart,
+    NextLevel,
+    Hint,
+    /// 关卡列表中的上一关
+    ListPrevious,
+    /// 关卡列表中的下一关
+    ListNext,
+    /// 打开或关闭设置菜单
+    Settings,
+}
+
+impl Action {
+    /// 所有动作
+    pub const ALL: [Action; 12] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Undo,
+        Action::Redo,
+        Action::Restart,
+        Action::NextLevel,
+        Action::Hint,
+        Action::ListPrevious,
+        Action::ListNext,
+        Action::Settings,
+    ];
+
+    /// 配置文件中使用的动作名称
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::Restart => "restart",
+            Action::NextLevel => "next_level",
+            Action::Hint => "hint",
+            Action::ListPrevious => "list_previous",
+            Action::ListNext => "list_next",
+            Action::Settings => "settings",
+        }
+    }
+
+    /// 根据配置文件中的名称查找动作
+    pub fn parse(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name.trim())
+    }
+
+    /// 移动动作对应的方向，其他动作返回 None
+    pub fn direction(self) -> Option<Direction> {
+        match self {
+            Action::MoveUp => Some(Direction::Up),
+            Action::MoveDown => Some(Direction::Down),
+            Action::MoveLeft => Some(Direction::Left),
+            Action::MoveRight => Some(Direction::Right),
+            _ => None,
+        }
+    }
+
+    /// 方向对应的移动动作
+    pub fn from_direction(direction: Direction) -> Action {
+        match direction {
+            Direction::Up => Action::MoveUp,
+            Direction::Down => Action::MoveDown,
+            Direction::Left => Action::MoveLeft,
+            Direction::Right => Action::MoveRight,
+        }
+    }
+}
+
+/// 内置的按键预设，只影响移动和撤销等动作的默认按键
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    /// 方向键移动
+    #[default]
+    Arrows,
+    /// WASD 移动
+    Wasd,
+    /// vi 风格的 hjkl 移动，u 撤销；h 被占用，提示改为 `/`
+    Hjkl,
+}
+
+/// 按住移动键时的自动重复设置，默认关闭（每次按键只走一步）
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default)]
+pub struct RepeatConfig {
+    pub enabled: bool,
+    /// 按住多久之后开始重复（毫秒）
+    pub delay_ms: u64,
+    /// 重复的速度（每秒步数）
+    pub rate: f32,
+    /// 是否允许重复推动箱子，关闭时走到箱子前就停止重复
+    pub push: bool,
+}
+
+impl Default for RepeatConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            delay_ms: 250,
+            rate: 15.0,
+            push: false,
+        }
+    }
+}
+
+impl RepeatConfig {
+    /// 开始重复之前的延迟
+    pub fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay_ms)
+    }
+}
+
+/// 配置文件的内容：先选择预设，再逐个覆盖动作的按键和手柄按钮（键为 `Action::name`），
+/// 以及按住移动键时的自动重复设置
+/// ```toml
+/// preset = "wasd"
+///
+/// [keys]
+/// undo = ["Z", "Back"]
+///
+/// [buttons]
+/// undo = ["East", "LeftTrigger2"]
+///
+/// [repeat]
+/// enabled = true
+/// ```
+#[derive(Default, Deserialize)]
+struct BindingsFile {
+    #[serde(default)]
+    preset: Preset,
+    #[serde(default)]
+    keys: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    buttons: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    repeat: RepeatConfig,
+}
+
+/// 手柄的输入状态，由手柄事件更新
+/// ggez 只为键盘记录“这一帧刚刚按下”，手柄按钮需要自己记录
+#[derive(Default)]
+struct GamepadState {
+    /// 上一帧之后按下的按钮对应的动作，下一帧开始时生效
+    pending: HashSet<Action>,
+    /// 这一帧刚刚按下的动作
+    just_pressed: HashSet<Action>,
+    /// 正被按住的按钮
+    held: HashSet<Button>,
+    /// 摇杆（或十字键轴）的偏移，向右、向上为正
+    stick: (f32, f32),
+    /// 摇杆当前指向的方向
+    stick_direction: Option<Direction>,
+}
+
+/// 按键绑定：每个动作可以绑定一个或多个按键和手柄按钮
+/// 键盘和手柄都通过这里翻译为动作，各个系统不区分输入来源
+pub struct KeyBindings {
+    keys: HashMap<Action, Vec<KeyCode>>,
+    buttons: HashMap<Action, Vec<Button>>,
+    /// 按住移动键时的自动重复设置
+    pub repeat: RepeatConfig,
+    gamepad: GamepadState,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings::preset(Preset::default())
+    }
+}
+
+impl KeyBindings {
+    /// 使用内置预设创建按键绑定，手柄按钮不受预设影响：
+    /// 十字键移动，B 撤销，X 重新开始，A 下一关，Y 重做，Select 提示，肩键切换关卡，Start 设置
+    pub fn preset(preset: Preset) -> KeyBindings {
+        let (moves, undo, hint) = match preset {
+            Preset::Arrows => ([KeyCode::Up, KeyCode::Down, KeyCode::Left, KeyCode::Right], KeyCode::Z, KeyCode::H),
+            Preset::Wasd => ([KeyCode::W, KeyCode::S, KeyCode::A, KeyCode::D], KeyCode::Z, KeyCode::H),
+            Preset::Hjkl => ([KeyCode::K, KeyCode::J, KeyCode::H, KeyCode::L], KeyCode::U, KeyCode::Slash),
+        };
+        let [up, down, left, right] = moves;
+        let keys = HashMap::from([
+            (Action::MoveUp, vec![up]),
+            (Action::MoveDown, vec![down]),
+            (Action::MoveLeft, vec![left]),
+            (Action::MoveRight, vec![right]),
+            (Action::Undo, vec![undo]),
+            (Action::Redo, vec![KeyCode::Y]),
+            (Action::Restart, vec![KeyCode::R]),
+            (Action::NextLevel, vec![KeyCode::Return]),
+            (Action::Hint, vec![hint]),
+            (Action::ListPrevious, vec![KeyCode::PageUp]),
+            (Action::ListNext, vec![KeyCode::PageDown]),
+            (Action::Settings, vec![KeyCode::F2]),
+        ]);
+        // 按钮名称采用 gilrs 的位置命名：South 对应 Xbox 手柄的 A 键，East 对应 B 键
+        let buttons = HashMap::from([
+            (Action::MoveUp, vec![Button::DPadUp]),
+            (Action::MoveDown, vec![Button::DPadDown]),
+            (Action::MoveLeft, vec![Button::DPadLeft]),
+            (Action::MoveRight, vec![Button::DPadRight]),
+            (Action::Undo, vec![Button::East]),
+            (Action::Redo, vec![Button::North]),
+            (Action::Restart, vec![Button::West]),
+            (Action::NextLevel, vec![Button::South]),
+            (Action::Hint, vec![Button::Select]),
+            (Action::ListPrevious, vec![Button::LeftTrigger]),
+            (Action::ListNext, vec![Button::RightTrigger]),
+            (Action::Settings, vec![Button::Start]),
+        ]);
+        KeyBindings {
+            keys,
+            buttons,
+            repeat: RepeatConfig::default(),
+            gamepad: GamepadState::default(),
+        }
+    }
+
+    /// 配置文件路径
+    pub fn path(ctx: &ggez::Context) -> PathBuf {
+        ctx.fs.user_data_dir().join(BINDINGS_FILE)
+    }
+
+    /// 读取按键配置，没有配置文件时使用方向键预设
+    /// 配置文件损坏时整体忽略，无法识别的按键名单独忽略，都只打印提示
+    pub fn load(ctx: &ggez::Context) -> KeyBindings {
+        let path = KeyBindings::path(ctx);
+        let Ok(text) = fs::read_to_string(&path) else {
+            return KeyBindings::default();
+        };
+        let file: BindingsFile = toml::from_str(&text).unwrap_or_else(|error| {
+            println!("ignoring corrupt key bindings {}: {}", path.display(), error);
+            BindingsFile::default()
+        });
+
+        let mut bindings = KeyBindings::preset(file.preset);
+        bindings.repeat = file.repeat;
+        if bindings.repeat.rate.is_nan() || bindings.repeat.rate <= 0.0 {
+            println!("ignoring invalid repeat rate {}", bindings.repeat.rate);
+            bindings.repeat.rate = RepeatConfig::default().rate;
+        }
+        for (action_name, names) in file.keys {
+            let Some(action) = Action::parse(&action_name) else {
+                println!("ignoring key bindings for unknown action '{}'", action_name);
+                continue;
+            };
+            let keys = names
+                .iter()
+                .filter_map(|name| {
+                    let key = parse_key(name);
+                    if key.is_none() {
+                        println!("ignoring unknown key '{}' for {}", name, action_name);
+                    }
+                    key
+                })
+                .collect();
+            bindings.keys.insert(action, keys);
+        }
+        for (action_name, names) in file.buttons {
+            let Some(action) = Action::parse(&action_name) else {
+                println!("ignoring button bindings for unknown action '{}'", action_name);
+                continue;
+            };
+            let buttons = names
+                .iter()
+                .filter_map(|name| {
+                    let button = parse_button(name);
+                    if button.is_none() {
+                        println!("ignoring unknown button '{}' for {}", name, action_name);
+                    }
+                    button
+                })
+                .collect();
+            bindings.buttons.insert(action, buttons);
+        }
+        bindings
+    }
+
+    /// 动作绑定的按键
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.keys.get(&action).map_or(&[], |keys| keys.as_slice())
+    }
+
+    /// 动作绑定的手柄按钮
+    pub fn buttons(&self, action: Action) -> &[Button] {
+        self.buttons.get(&action).map_or(&[], |buttons| buttons.as_slice())
+    }
+
+    /// 动作的任意一个按键或手柄按钮是否在这一帧刚刚按下
+    pub fn is_just_pressed(&self, ctx: &ggez::Context, action: Action) -> bool {
+        self.keys(action).iter().any(|&key| ctx.keyboard.is_key_just_pressed(key))
+            || self.gamepad.just_pressed.contains(&action)
+    }
+
+    /// 指定方向的任意一个移动键或手柄按钮是否正被按住，或者摇杆正指向该方向
+    pub fn is_direction_pressed(&self, ctx: &ggez::Context, direction: Direction) -> bool {
+        let action = Action::from_direction(direction);
+        self.keys(action).iter().any(|&key| ctx.keyboard.is_key_pressed(key))
+            || self.buttons(action).iter().any(|button| self.gamepad.held.contains(button))
+            || self.gamepad.stick_direction == Some(direction)
+    }
+
+    /// 这一帧刚刚按下的移动动作对应的方向
+    pub fn just_pressed_direction(&self, ctx: &ggez::Context) -> Option<Direction> {
+        Action::ALL
+            .into_iter()
+            .filter(|&action| self.is_just_pressed(ctx, action))
+            .find_map(Action::direction)
+    }
+
+    /// 每帧开始时调用：上一帧之后按下的手柄按钮在这一帧算作刚刚按下
+    pub fn begin_frame(&mut self) {
+        self.gamepad.just_pressed = std::mem::take(&mut self.gamepad.pending);
+    }
+
+    /// 手柄按钮按下
+    pub fn press_button(&mut self, button: Button) {
+        self.gamepad.held.insert(button);
+        for action in Action::ALL {
+            if self.buttons(action).contains(&button) {
+                self.gamepad.pending.insert(action);
+            }
+        }
+    }
+
+    /// 手柄按钮松开
+    pub fn release_button(&mut self, button: Button) {
+        self.gamepad.held.remove(&button);
+    }
+
+    /// 左摇杆或十字键轴移动：偏移较大的轴超过阈值时视为按住该方向，
+    /// 方向改变时算作按下新方向的移动动作
+    pub fn move_axis(&mut self, axis: Axis, value: f32) {
+        let stick = &mut self.gamepad.stick;
+        match axis {
+            Axis::LeftStickX | Axis::DPadX => stick.0 = value,
+            Axis::LeftStickY | Axis::DPadY => stick.1 = value,
+            _ => return,
+        }
+
+        let (x, y) = *stick;
+        let direction = if x.abs().max(y.abs()) < STICK_THRESHOLD {
+            None
+        } else if x.abs() > y.abs() {
+            Some(if x > 0.0 { Direction::Right } else { Direction::Left })
+        } else {
+            Some(if y > 0.0 { Direction::Up } else { Direction::Down })
+        };
+        if direction != self.gamepad.stick_direction {
+            self.gamepad.stick_direction = direction;
+            if let Some(direction) = direction {
+                self.gamepad.pending.insert(Action::from_direction(direction));
+            }
+        }
+    }
+
+    /// 动作的第一个按键的名称，用于界面提示；没有绑定按键时返回 "-"
+    pub fn key_name(&self, action: Action) -> String {
+        self.keys(action).first().map_or("-".to_string(), |key| format!("{:?}", key))
+    }
+}
+
+/// 配置文件中可以使用的按键，名称与 `KeyCode` 的变体名相同（不区分大小写）
+const NAMED_KEYS: [KeyCode; 82] = [
+    KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G, KeyCode::H, KeyCode::I,
+    KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N, KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R,
+    KeyCode::S, KeyCode::T, KeyCode::U, KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y, KeyCode::Z,
+    KeyCode::Key0, KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4,
+    KeyCode::Key5, KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9,
+    KeyCode::Numpad0, KeyCode::Numpad1, KeyCode::Numpad2, KeyCode::Numpad3, KeyCode::Numpad4,
+    KeyCode::Numpad5, KeyCode::Numpad6, KeyCode::Numpad7, KeyCode::Numpad8, KeyCode::Numpad9,
+    KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6,
+    KeyCode::F7, KeyCode::F8, KeyCode::F9, KeyCode::F10, KeyCode::F11, KeyCode::F12,
+    KeyCode::Up, KeyCode::Down, KeyCode::Left, KeyCode::Right,
+    KeyCode::Home, KeyCode::End, KeyCode::PageUp, KeyCode::PageDown, KeyCode::Insert, KeyCode::Delete,
+    KeyCode::Back, KeyCode::Return, KeyCode::NumpadEnter, KeyCode::Space, KeyCode::Tab,
+    KeyCode::Minus, KeyCode::Equals, KeyCode::Comma, KeyCode::Period, KeyCode::Slash,
+    KeyCode::Semicolon, KeyCode::Apostrophe, KeyCode::LBracket, KeyCode::RBracket,
+];
+
+/// 根据名称查找按键，例如 `W`、`Up`、`Return`、`Key1`
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    NAMED_KEYS
+        .into_iter()
+        .find(|key| format!("{:?}", key).eq_ignore_ascii_case(name.trim()))
+}
+
+/// 配置文件中可以使用的手柄按钮，名称与 `Button` 的变体名相同（不区分大小写）
+const NAMED_BUTTONS: [Button; 19] = [
+    Button::South, Button::East, Button::North, Button::West, Button::C, Button::Z,
+    Button::LeftTrigger, Button::LeftTrigger2, Button::RightTrigger, Button::RightTrigger2,
+    Button::Select, Button::Start, Button::Mode, Button::LeftThumb, Button::RightThumb,
+    Button::DPadUp, Button::DPadDown, Button::DPadLeft, Button::DPadRight,
+];
+
+/// 根据名称查找手柄按钮，例如 `South`、`LeftTrigger`、`DPadUp`
+pub fn parse_button(name: &str) -> Option<Button> {
+    NAMED_BUTTONS
+        .into_iter()
+        .find(|button| format!("{:?}", button).eq_ignore_ascii_case(name.trim()))
+}
@@ -1,26 +1,33 @@
-use std::{collections::HashMap, fmt::Display, time::Duration};
+use std::{collections::{HashMap, VecDeque}, fmt::Display, thread::JoinHandle, time::Duration};
 
-use ggez::audio::{self, SoundSource};
+use ggez::{audio::{self, SoundSource}, graphics::{Color, Image, InstanceArray, Rect}};
+use hecs::Entity;
+use serde::{Deserialize, Serialize};
 
-use crate::systems::events::Event;
+use crate::{atlas::Atlas, grid::Direction, solver::{Solution, SolveError, SolverMove}, theme::Theme};
 
 /// 位置组件，表示实体在网格中的坐标
-#[derive(Clone,Copy,PartialEq,Eq,Hash)]
+#[derive(Clone,Copy,PartialEq,Eq,Hash,Serialize,Deserialize)]
 pub struct Position {
     pub x: u8,
     pub y: u8,
     pub z: u8,
 }
 
-/// 渲染组件，包含渲染所需的资源路径
+/// 渲染组件，记录实体绘制为哪种图块，具体的图片由当前主题决定
 pub struct Renderable {
-    pub paths: Vec<String>,
+    pub tile: Tile,
 }
 
-/// 渲染类型枚举：静态图片或动画
-pub enum RenderableKind {
-    Static,
-    Animated,
+/// 图块种类：地图上可以绘制的逻辑图块
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Tile {
+    Wall,
+    Floor,
+    Player,
+    Box(BoxColor),
+    /// 目标点
+    Goal(BoxColor),
 }
 
 /// 墙组件（标记组件）
@@ -46,7 +53,7 @@ pub struct Moveable {}
 pub struct Immovable {}
 
 /// 游戏状态枚举
-#[derive(Default, PartialEq, Eq)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
 pub enum GameplayState {
     #[default]
     Playing, // 游戏中
@@ -54,11 +61,108 @@ pub enum GameplayState {
     Lost,    // 失败
 }
 
+/// 推箱子规则
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Ruleset {
+    /// 经典规则：一次只能推动一个箱子
+    Classic,
+    /// 多箱推动：可以推动一整排箱子
+    #[default]
+    MultiPush,
+}
+
+impl Ruleset {
+    /// 从关卡包中的文本解析规则（不区分大小写）
+    pub fn parse(text: &str) -> Option<Ruleset> {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "classic" | "single" => Some(Ruleset::Classic),
+            "multipush" | "multi-push" | "multi" => Some(Ruleset::MultiPush),
+            _ => None,
+        }
+    }
+
+    /// 切换到另一种规则
+    pub fn toggled(self) -> Ruleset {
+        match self {
+            Ruleset::Classic => Ruleset::MultiPush,
+            Ruleset::MultiPush => Ruleset::Classic,
+        }
+    }
+}
+
+impl Display for Ruleset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Ruleset::Classic => "Classic",
+            Ruleset::MultiPush => "Multi-push",
+        })?;
+        Ok(())
+    }
+}
+
 /// 游戏核心状态组件，记录当前状态和步数
+/// 步数等统计只通过 `record_step` / `undo_step` 修改，保证与移动记录一致
 #[derive(Default)]
 pub struct GamePlay {
     pub state: GameplayState,
-    pub move_count: u32
+    pub move_count: u32,
+    /// 推动次数（一次推动一排箱子也只算一次）
+    pub push_count: u32,
+    /// 箱子直线段数：连续朝同一方向推动同一个箱子算一段
+    pub box_lines: u32,
+    /// 玩家直线段数：连续朝同一方向移动算一段
+    pub player_lines: u32,
+    /// 本关的移动记录（LURD 记法），通关后可以导出为解法
+    pub move_log: String,
+}
+
+impl GamePlay {
+    /// 记录一步移动（LURD 字符），更新步数、推动次数和直线段数
+    pub fn record_step(&mut self, step: char) {
+        let (player_line, box_line) = self.continues_line(step);
+        self.move_count += 1;
+        if step.is_ascii_uppercase() {
+            self.push_count += 1;
+            if !box_line {
+                self.box_lines += 1;
+            }
+        }
+        if !player_line {
+            self.player_lines += 1;
+        }
+        self.move_log.push(step);
+    }
+
+    /// 撤销最后一步移动的统计，返回被撤销的 LURD 字符
+    pub fn undo_step(&mut self) -> Option<char> {
+        let step = self.move_log.pop()?;
+        let (player_line, box_line) = self.continues_line(step);
+        self.move_count = self.move_count.saturating_sub(1);
+        if step.is_ascii_uppercase() {
+            self.push_count = self.push_count.saturating_sub(1);
+            if !box_line {
+                self.box_lines = self.box_lines.saturating_sub(1);
+            }
+        }
+        if !player_line {
+            self.player_lines = self.player_lines.saturating_sub(1);
+        }
+        Some(step)
+    }
+
+    /// 判断紧接在移动记录之后的一步是否延续了玩家直线段和箱子直线段
+    /// 同一方向的连续推动推的必然是同一个箱子（或同一排箱子）
+    fn continues_line(&self, step: char) -> (bool, bool) {
+        let (Some(last), Some(step)) = (
+            self.move_log.chars().last().and_then(SolverMove::from_lurd),
+            SolverMove::from_lurd(step),
+        ) else {
+            return (false, false);
+        };
+        let same_direction = last.direction == step.direction;
+        (same_direction, same_direction && last.push && step.push)
+    }
 }
 
 impl Display for GameplayState {
@@ -73,7 +177,8 @@ impl Display for GameplayState {
 }
 
 /// 箱子颜色枚举
-#[derive(PartialEq)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum BoxColor {
     Red,
     Blue
@@ -90,32 +195,51 @@ impl Display for BoxColor {
 }
 
 impl Renderable {
-    /// 创建静态渲染组件（单张图片）
-    pub fn new_static(path: &str) -> Self{
+    /// 创建渲染组件
+    pub fn new(tile: Tile) -> Self{
         Self{
-            paths: vec![path.to_string()],
+            tile,
         }
     }
+}
 
-    /// 创建动画渲染组件（多张图片）
-    pub fn new_animated(paths: Vec<String>) -> Self{
-        Self{
-            paths,
+impl Tile {
+    /// 所有图块
+    pub const ALL: [Tile; 7] = [
+        Tile::Wall,
+        Tile::Floor,
+        Tile::Player,
+        Tile::Box(BoxColor::Red),
+        Tile::Box(BoxColor::Blue),
+        Tile::Goal(BoxColor::Red),
+        Tile::Goal(BoxColor::Blue),
+    ];
+
+    /// 主题清单中使用的图块名称，例如 `wall`、`box_red`、`goal_blue`
+    pub fn name(self) -> String {
+        match self {
+            Tile::Wall => "wall".to_string(),
+            Tile::Floor => "floor".to_string(),
+            Tile::Player => "player".to_string(),
+            Tile::Box(color) => format!("box_{}", color),
+            Tile::Goal(color) => format!("goal_{}", color),
         }
     }
 
-    /// 获取渲染类型
-    pub fn kind(&self) -> RenderableKind{
-        match self.paths.len() {
-            0 => panic!("Invalid renderable kind"),
-            1 => RenderableKind::Static,
-            _ => RenderableKind::Animated,
-        }
+    /// 根据主题清单中的名称查找图块
+    pub fn parse(name: &str) -> Option<Tile> {
+        Tile::ALL.into_iter().find(|tile| tile.name() == name.trim())
     }
 
-    /// 获取指定索引的资源路径（用于动画循环）
-    pub fn path(&self,path_index: usize) -> String {
-        self.paths[path_index % self.paths.len()].clone()
+    /// 主题没有指定时使用的帧名
+    pub fn default_frames(self) -> Vec<String> {
+        match self {
+            Tile::Wall => vec!["wall".to_string()],
+            Tile::Floor => vec!["floor".to_string()],
+            Tile::Player => vec!["player_1".to_string(), "player_2".to_string(), "player_3".to_string()],
+            Tile::Box(color) => vec![format!("box_{}_1", color), format!("box_{}_2", color)],
+            Tile::Goal(color) => vec![format!("box_spot_{}", color)],
+        }
     }
 }
 
@@ -125,10 +249,92 @@ pub struct Time {
     pub delta: Duration
 }
 
-/// 事件队列组件，存储待处理的游戏事件
+/// 一步操作的记录：本步中移动的实体及其移动前后的位置
+pub struct MoveRecord {
+    pub moves: Vec<(Entity, Position, Position)>,
+    /// 本步的 LURD 字符，撤销/重做时同步更新移动记录
+    pub step: char,
+}
+
+/// 历史记录组件，保存撤销栈和重做栈
+#[derive(Default)]
+pub struct History {
+    pub undo: Vec<MoveRecord>,
+    pub redo: Vec<MoveRecord>,
+}
+
+/// 求解器状态组件，保存最近一次求解结果的描述，用于界面显示
+#[derive(Default)]
+pub struct SolverStatus {
+    pub message: String,
+}
+
+/// 后台搜索的用途：搜索结束后据此决定如何使用结果
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SearchPurpose {
+    /// 提示键：显示到下一次推动为止的路径
+    Hint,
+    /// F5：在右侧面板显示完整解的步数
+    Solve,
+}
+
+/// 正在工作线程中运行的一次求解
+pub struct SearchTask {
+    pub purpose: SearchPurpose,
+    /// 开始搜索时的移动记录，结束时局面已经变化则丢弃结果
+    pub move_log: String,
+    pub handle: JoinHandle<Result<Solution, SolveError>>,
+}
+
+/// 后台搜索组件：提示和求解在工作线程中进行，游戏循环每帧检查是否完成，不会卡住画面
+#[derive(Default)]
+pub struct Search {
+    pub task: Option<SearchTask>,
+}
+
+/// 提示组件，保存下一次推动的提示：玩家要走的路径和要推的箱子
+#[derive(Default)]
+pub struct Hint {
+    /// 玩家推动前需要依次经过的格子
+    pub path: Vec<Position>,
+    /// 要推动的箱子位置和推动方向
+    pub push: Option<(Position, Direction)>,
+}
+
+/// 按住移动键自动重复的状态
+#[derive(Default)]
+pub struct KeyRepeat {
+    /// 正在按住的移动方向，松开或停止重复后为 None
+    pub direction: Option<Direction>,
+    /// 已经按住的时间
+    pub held: Duration,
+    /// 已经处理到的重复步数（包括卡顿时跳过的步数）
+    pub repeats: u32,
+}
+
+/// 点击移动组件：点击棋盘后玩家沿最短路径逐步行走，或把选中的箱子推到点击的格子
 #[derive(Default)]
-pub struct EventQueue {
-    pub events: Vec<Event>,
+pub struct ClickMove {
+    /// 还没有执行的移动方向，为空时没有在行走
+    pub steps: VecDeque<Direction>,
+    /// 选中的箱子，下一次点击的格子作为它的目的地
+    pub selected_box: Option<Position>,
+    /// 距离上一步经过的时间
+    pub elapsed: Duration,
+    /// 最近一次点击的走不到的格子，以及提示还要显示多久
+    pub unreachable: Option<(Position, Duration)>,
+}
+
+/// 回放组件，存在时游戏处于回放模式：方向键等按键只控制回放，不再移动玩家
+/// 回放在重新开始的关卡上逐步执行解法，`position` 为已经执行的步数
+pub struct Replay {
+    pub moves: Vec<SolverMove>,
+    pub position: usize,
+    pub paused: bool,
+    /// 回放速度（每秒步数）
+    pub speed: f32,
+    /// 距离上一步经过的时间
+    pub elapsed: Duration,
 }
 
 /// 音频存储组件，管理游戏音效
@@ -137,6 +343,130 @@ pub struct AudioStore {
     pub sounds: HashMap<String, std::boxed::Box<audio::Source>>
 }
 
+/// 设置菜单组件：菜单打开时存在，关闭时删除
+pub struct SettingsMenu {
+    /// 可以选择的主题（目录名, 显示名称）
+    pub themes: Vec<(String, String)>,
+    /// 当前高亮的主题
+    pub selected: usize,
+}
+
+/// 精灵：一帧所在的贴图和它在贴图中的位置
+#[derive(Clone)]
+pub struct Sprite {
+    /// 贴图在资源目录中的路径
+    pub texture: String,
+    /// 归一化（0 到 1）的源矩形，单独加载的帧为整张图片
+    pub src: Rect,
+    /// 帧的像素宽度，用于缩放到格子大小
+    pub width: f32,
+}
+
+/// 占位图在贴图缓存中的键，不会与资源路径冲突
+const PLACEHOLDER_TEXTURE: &str = "<placeholder>";
+
+/// 图片存储组件，缓存当前主题的贴图、帧的位置和批量绘制数组
+/// 帧优先从主题的精灵图集中查找，图集中没有的帧单独加载主题目录下的 `<帧名>.png`；
+/// 加载关卡时一次性读取，避免每帧重新解码 PNG。批量绘制数组在帧之间复用，每帧只替换其中的实例
+#[derive(Default)]
+pub struct ImageStore {
+    pub theme: Theme,
+    pub atlas: Option<Atlas>,
+    pub images: HashMap<String, Image>,
+    pub sprites: HashMap<String, Sprite>,
+    /// 按 (贴图, 该贴图在本帧中的第几个批次) 缓存的批量绘制数组
+    pub batches: HashMap<(String, usize), InstanceArray>,
+}
+
+impl ImageStore {
+    /// 切换主题：读取主题的图集并清空所有缓存
+    pub fn set_theme(&mut self, ctx: &ggez::Context, theme: Theme) {
+        self.atlas = Atlas::load(ctx, &theme.atlas);
+        self.theme = theme;
+        self.images.clear();
+        self.sprites.clear();
+        self.batches.clear();
+    }
+
+    /// 获取指定路径的贴图，缓存中没有时从资源目录加载，文件不存在或无法解码时返回 None
+    pub fn image(&mut self, ctx: &ggez::Context, path: &str) -> Option<Image> {
+        if let Some(image) = self.images.get(path) {
+            return Some(image.clone());
+        }
+        let image = Image::from_path(ctx, path).ok()?;
+        self.images.insert(path.to_string(), image.clone());
+        Some(image)
+    }
+
+    /// 获取帧对应的精灵，第一次使用时查找图集或加载单独的图片
+    /// 当前主题缺少该帧时改用默认主题的帧，默认主题也没有时使用占位图（缺少的帧在加载主题时已经提示过）
+    pub fn sprite(&mut self, ctx: &ggez::Context, frame: &str) -> Sprite {
+        if let Some(sprite) = self.sprites.get(frame) {
+            return sprite.clone();
+        }
+
+        let atlas_frame = self
+            .atlas
+            .as_ref()
+            .and_then(|atlas| Some((atlas.image.clone(), atlas.frame(frame)?)));
+        let image_path = self.theme.frame_image_path(frame);
+        let sprite = match self.find_sprite(ctx, atlas_frame, image_path) {
+            Some(sprite) => sprite,
+            None => {
+                let theme = Theme::default();
+                let atlas_frame = Atlas::load(ctx, &theme.atlas)
+                    .and_then(|atlas| Some((atlas.image.clone(), atlas.frame(frame)?)));
+                self.find_sprite(ctx, atlas_frame, theme.frame_image_path(frame))
+                    .unwrap_or_else(|| self.placeholder(ctx))
+            }
+        };
+        self.sprites.insert(frame.to_string(), sprite.clone());
+        sprite
+    }
+
+    /// 从图集中的矩形或单独的图片创建精灵，两者都不可用时返回 None
+    fn find_sprite(&mut self, ctx: &ggez::Context, atlas_frame: Option<(String, Rect)>, image_path: String) -> Option<Sprite> {
+        let atlas_image = atlas_frame.and_then(|(texture, rect)| Some((self.image(ctx, &texture)?, texture, rect)));
+        if let Some((image, texture, rect)) = atlas_image {
+            let (width, height) = (image.width() as f32, image.height() as f32);
+            return Some(Sprite {
+                texture,
+                src: Rect::new(rect.x / width, rect.y / height, rect.w / width, rect.h / height),
+                width: rect.w,
+            });
+        }
+        let width = self.image(ctx, &image_path)?.width() as f32;
+        Some(Sprite {
+            texture: image_path,
+            src: Rect::one(),
+            width,
+        })
+    }
+
+    /// 找不到帧时使用的占位精灵：一个品红色的色块
+    fn placeholder(&mut self, ctx: &ggez::Context) -> Sprite {
+        let image = Image::from_color(ctx, 1, 1, Some(Color::MAGENTA));
+        self.images.insert(PLACEHOLDER_TEXTURE.to_string(), image);
+        Sprite {
+            texture: PLACEHOLDER_TEXTURE.to_string(),
+            src: Rect::one(),
+            width: 1.0,
+        }
+    }
+
+    /// 获取贴图的第 `index` 个批量绘制数组，第一次使用时创建
+    /// 同一帧内同一贴图的多个批次使用不同的数组，否则后绘制的批次会覆盖先绘制的实例
+    /// 贴图总是先经过 `sprite` 加载，所以这里一定能在缓存中找到
+    pub fn batch(&mut self, ctx: &ggez::Context, texture: &str, index: usize) -> &mut InstanceArray {
+        let key = (texture.to_string(), index);
+        if !self.batches.contains_key(&key) {
+            let image = self.images[texture].clone();
+            self.batches.insert(key.clone(), InstanceArray::new(ctx, image));
+        }
+        self.batches.get_mut(&key).unwrap()
+    }
+}
+
 impl AudioStore {
     /// 播放指定名称的音效
     pub fn play(&mut self,ctx: &mut ggez::Context, sound: &str){
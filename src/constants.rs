@@ -1,3 +1,3 @@
 pub const TITLE_WIDTH: f32 = 32.0;
-pub const MAP_WIDTH: u8 = 8;
-pub const MAP_HEIGHT: u8 = 9;
+pub const BOARD_WIDTH: f32 = 500.0;
+pub const BOARD_HEIGHT: f32 = 490.0;
@@ -0,0 +1,220 @@
+use std::collections::{HashMap, HashSet};
+
+use hecs::World;
+
+use crate::{component::{Box, BoxColor, Position, Ruleset}, grid::{Direction, Grid}, solver::{Board, UNREACHABLE}};
+
+/// 死锁分析组件
+/// 关卡加载时预先计算每种颜色的死格，每次推动后检测冻结死锁
+#[derive(Default)]
+pub struct DeadlockAnalysis {
+    grid: Grid,
+    ruleset: Ruleset,
+    walls: Vec<bool>,
+    goals: Vec<Option<BoxColor>>,
+    /// 每种颜色的死格：该颜色的箱子一旦进入，就再也推不到同色目标点
+    dead: HashMap<BoxColor, Vec<bool>>,
+    /// 箱子数量与目标点数量相同的颜色，这些颜色的每个箱子最终都必须归位
+    exact: HashSet<BoxColor>,
+}
+
+impl DeadlockAnalysis {
+    /// 根据棋盘预先计算死格
+    pub fn new(board: &Board) -> DeadlockAnalysis {
+        let cell_count = board.cell_count();
+        let dead = board
+            .push_distances()
+            .into_iter()
+            .map(|(color, distance)| (color, distance.iter().map(|&d| d == UNREACHABLE).collect()))
+            .collect();
+
+        let mut box_counts: HashMap<BoxColor, usize> = HashMap::new();
+        let mut goal_counts: HashMap<BoxColor, usize> = HashMap::new();
+        for (_, color) in board.boxes().iter() {
+            *box_counts.entry(*color).or_default() += 1;
+        }
+        for cell in 0..cell_count {
+            if let Some(color) = board.goal(cell) {
+                *goal_counts.entry(color).or_default() += 1;
+            }
+        }
+        let exact = goal_counts
+            .into_iter()
+            .filter(|(color, goals)| box_counts.get(color) == Some(goals))
+            .map(|(color, _)| color)
+            .collect();
+
+        DeadlockAnalysis {
+            grid: board.grid,
+            ruleset: board.ruleset,
+            walls: (0..cell_count).map(|cell| board.is_wall(cell)).collect(),
+            goals: (0..cell_count).map(|cell| board.goal(cell)).collect(),
+            dead,
+            exact,
+        }
+    }
+
+    /// 从 ECS 世界读取棋盘并计算，关卡不完整（例如没有玩家）时不做任何检测
+    pub fn from_world(world: &World) -> DeadlockAnalysis {
+        Board::from_world(world)
+            .map(|board| DeadlockAnalysis::new(&board))
+            .unwrap_or_default()
+    }
+
+    /// 判断格子对于指定颜色的箱子是否是死格
+    pub fn is_dead_square(&self, cell: usize, color: BoxColor) -> bool {
+        self.exact.contains(&color) && self.dead.get(&color).is_some_and(|dead| dead[cell])
+    }
+
+    /// 检查当前局面是否已经死锁
+    pub fn is_deadlocked(&self, world: &World) -> bool {
+        let boxes: Vec<(usize, BoxColor)> = world
+            .query::<(&Position, &Box)>()
+            .iter()
+            .filter_map(|(_, (position, the_box))| Some((self.grid.index(position)?, the_box.color)))
+            .collect();
+        self.find_deadlock(&boxes).is_some()
+    }
+
+    /// 查找处于死锁的箱子，返回其所在格子
+    /// 死锁包括：箱子在该颜色的死格上，或者箱子被冻结（横竖都推不动）且不在同色目标点上
+    pub fn find_deadlock(&self, boxes: &[(usize, BoxColor)]) -> Option<usize> {
+        if self.walls.is_empty() {
+            return None;
+        }
+        let by_cell: HashMap<usize, BoxColor> = boxes.iter().copied().collect();
+
+        boxes
+            .iter()
+            .filter(|(_, color)| self.exact.contains(color))
+            .find(|(cell, color)| {
+                if self.goals[*cell] == Some(*color) {
+                    return false;
+                }
+                self.is_dead_square(*cell, *color) || self.is_frozen(*cell, &by_cell, &mut HashSet::new())
+            })
+            .map(|(cell, _)| *cell)
+    }
+
+    /// 判断箱子是否被冻结：水平和竖直方向都无法推动
+    /// `visiting` 中的箱子正在检查，视为墙，避免相邻箱子之间无限递归
+    fn is_frozen(&self, cell: usize, boxes: &HashMap<usize, BoxColor>, visiting: &mut HashSet<usize>) -> bool {
+        visiting.insert(cell);
+        let frozen = self.is_axis_blocked(cell, Direction::Left, boxes, visiting)
+            && self.is_axis_blocked(cell, Direction::Up, boxes, visiting);
+        visiting.remove(&cell);
+        frozen
+    }
+
+    /// 判断箱子在某个轴向（左右或上下）上是否无法推动
+    fn is_axis_blocked(&self, cell: usize, direction: Direction, boxes: &HashMap<usize, BoxColor>, visiting: &mut HashSet<usize>) -> bool {
+        let sides = [self.grid.step(cell, direction), self.grid.step(cell, direction.opposite())];
+
+        // 任意一侧是墙（或地图外），这个方向就推不动
+        if sides.iter().any(|side| side.is_none_or(|side| self.walls[side])) {
+            return true;
+        }
+        let sides = sides.map(|side| side.unwrap());
+
+        // 两侧都是该颜色的死格，推动只会制造死锁
+        let color = boxes[&cell];
+        if sides.iter().all(|&side| self.is_dead_square(side, color)) {
+            return true;
+        }
+
+        // 多箱推动时相邻的箱子可以被一起推走，不能据此判定冻结
+        if self.ruleset == Ruleset::MultiPush {
+            return false;
+        }
+
+        // 任意一侧是被冻结的箱子，这个方向也推不动
+        sides.iter().any(|&side| {
+            boxes.contains_key(&side) && (visiting.contains(&side) || self.is_frozen(side, boxes, visiting))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use hecs::World;
+
+    use super::DeadlockAnalysis;
+    use crate::{
+        component::{BoxColor, Position, Ruleset},
+        entity,
+        map::load_level_map,
+        solver::Board,
+    };
+
+    /// 目标点在左墙中间，箱子在房间中间
+    const ROOM: &str = "
+        #######
+        #     #
+        #.$ @ #
+        #     #
+        #######
+    ";
+
+    /// 两个箱子并排贴着上墙，两边各有一个目标点
+    const PAIR: &str = "
+        ########
+        # .$$. #
+        #      #
+        #  @   #
+        ########
+    ";
+
+    fn analysis_from(map: &str, ruleset: Ruleset) -> (World, Board, DeadlockAnalysis) {
+        let mut world = World::new();
+        assert!(load_level_map(&mut world, map.to_string()).is_ok(), "invalid test map");
+        entity::create_ruleset(&mut world, ruleset);
+        let board = Board::from_world(&world).unwrap();
+        let analysis = DeadlockAnalysis::new(&board);
+        (world, board, analysis)
+    }
+
+    fn cell(board: &Board, x: u8, y: u8) -> usize {
+        board.grid.index(&Position { x, y, z: 0 }).unwrap()
+    }
+
+    #[test]
+    fn corners_and_walls_without_goals_are_dead() {
+        let (_, board, analysis) = analysis_from(ROOM, Ruleset::Classic);
+        // 角落
+        assert!(analysis.is_dead_square(cell(&board, 1, 1), BoxColor::Blue));
+        assert!(analysis.is_dead_square(cell(&board, 5, 3), BoxColor::Blue));
+        // 沿着没有目标点的墙
+        assert!(analysis.is_dead_square(cell(&board, 3, 1), BoxColor::Blue));
+        assert!(analysis.is_dead_square(cell(&board, 5, 2), BoxColor::Blue));
+    }
+
+    #[test]
+    fn wall_square_with_a_goal_is_live() {
+        let (_, board, analysis) = analysis_from(ROOM, Ruleset::Classic);
+        assert!(!analysis.is_dead_square(cell(&board, 1, 2), BoxColor::Blue));
+        assert!(!analysis.is_dead_square(cell(&board, 3, 2), BoxColor::Blue));
+        assert_eq!(analysis.find_deadlock(board.boxes()), None);
+    }
+
+    #[test]
+    fn classic_pair_against_a_wall_is_frozen() {
+        let (world, board, analysis) = analysis_from(PAIR, Ruleset::Classic);
+        // 贴墙的格子本身不是死格（这一行有目标点），死锁来自两个箱子互相挡住
+        assert!(!analysis.is_dead_square(cell(&board, 3, 1), BoxColor::Blue));
+        let boxes = board.boxes().iter().copied().collect();
+        assert!(analysis.is_frozen(cell(&board, 3, 1), &boxes, &mut HashSet::new()));
+        assert!(analysis.find_deadlock(board.boxes()).is_some());
+        assert!(analysis.is_deadlocked(&world));
+    }
+
+    #[test]
+    fn multi_push_pair_is_not_frozen() {
+        let (world, board, analysis) = analysis_from(PAIR, Ruleset::MultiPush);
+        let boxes = board.boxes().iter().copied().collect();
+        assert!(!analysis.is_frozen(cell(&board, 3, 1), &boxes, &mut HashSet::new()));
+        assert_eq!(analysis.find_deadlock(board.boxes()), None);
+        assert!(!analysis.is_deadlocked(&world));
+    }
+}
@@ -1,13 +1,16 @@
+use std::{collections::HashSet, time::Duration};
+
 use crate::component::{
-    AudioStore, Box, BoxColor, BoxSpot, EventQueue, GamePlay, Immovable, Moveable, Player, Position, Renderable, Time, Wall
+    AudioStore, Box, BoxColor, BoxSpot, ClickMove, GamePlay, Hint, History, ImageStore, Immovable, KeyRepeat, Moveable, Player, Position, Renderable, Replay, Ruleset, Search, SettingsMenu, SolverStatus, Tile, Time, Wall
 };
+use crate::{deadlock::DeadlockAnalysis, grid::Grid, map::MapError, solver::SolverMove, systems::replay::DEFAULT_REPLAY_SPEED, theme::Theme};
 use ggez::audio::Source;
 use hecs::{Entity, World};
 
 pub fn create_wall(world: &mut World, position: &Position) -> Entity {
     world.spawn((
         Position { z: 10, ..*position },
-        Renderable::new_static("/images/wall.png"),
+        Renderable::new(Tile::Wall),
         Wall {},
         Immovable {},
     ))
@@ -15,17 +18,14 @@ pub fn create_wall(world: &mut World, position: &Position) -> Entity {
 pub fn create_floor(world: &mut World, position: &Position) -> Entity {
     world.spawn((
         Position { z: 5, ..*position },
-        Renderable::new_static("/images/floor.png"),
+        Renderable::new(Tile::Floor),
     ))
 }
 
 pub fn create_box(world: &mut World, position: &Position, color: BoxColor) -> Entity {
     world.spawn((
         Position { z: 10, ..*position },
-        Renderable::new_animated(vec![
-            format!("/images/box_{}_1.png", color),
-            format!("/images/box_{}_2.png", color),
-        ]),
+        Renderable::new(Tile::Box(color)),
         Box { color },
         Moveable {},
     ))
@@ -34,7 +34,7 @@ pub fn create_box(world: &mut World, position: &Position, color: BoxColor) -> En
 pub fn create_box_spot(world: &mut World, position: &Position, color: BoxColor) -> Entity {
     world.spawn((
         Position { z: 9, ..*position },
-        Renderable::new_static(&format!("/images/box_spot_{}.png", color)),
+        Renderable::new(Tile::Goal(color)),
         BoxSpot { color },
     ))
 }
@@ -42,11 +42,7 @@ pub fn create_box_spot(world: &mut World, position: &Position, color: BoxColor)
 pub fn create_player(world: &mut World, position: &Position) -> Entity {
     world.spawn((
         Position { z: 10, ..*position },
-        Renderable::new_animated(vec![
-            "/images/player_1.png".to_string(),
-            "/images/player_2.png".to_string(),
-            "/images/player_3.png".to_string(),
-        ]),
+        Renderable::new(Tile::Player),
         Player {},
         Moveable {},
     ))
@@ -56,18 +52,87 @@ pub fn create_gameplay(world: &mut World) -> Entity {
     world.spawn((GamePlay::default(),))
 }
 
+pub fn create_grid(world: &mut World, width: usize, height: usize) -> Entity {
+    world.spawn((Grid::new(width, height),))
+}
+
+pub fn create_deadlock_analysis(world: &mut World) -> Entity {
+    let analysis = DeadlockAnalysis::from_world(world);
+    world.spawn((analysis,))
+}
+
+pub fn create_map_error(world: &mut World, error: MapError) -> Entity {
+    world.spawn((error,))
+}
+
+pub fn create_ruleset(world: &mut World, ruleset: Ruleset) -> Entity {
+    world.spawn((ruleset,))
+}
+
 pub fn create_time(world:&mut World)-> Entity{
     world.spawn((Time::default(),))
 }
 
-pub fn create_event_queue(world: &mut World) -> Entity {
-    world.spawn((EventQueue::default(),))
+pub fn create_history(world: &mut World) -> Entity {
+    world.spawn((History::default(),))
+}
+
+pub fn create_solver_status(world: &mut World) -> Entity {
+    world.spawn((SolverStatus::default(),))
+}
+
+pub fn create_hint(world: &mut World) -> Entity {
+    world.spawn((Hint::default(),))
+}
+
+pub fn create_search(world: &mut World) -> Entity {
+    world.spawn((Search::default(),))
+}
+
+pub fn create_key_repeat(world: &mut World) -> Entity {
+    world.spawn((KeyRepeat::default(),))
+}
+
+pub fn create_click_move(world: &mut World) -> Entity {
+    world.spawn((ClickMove::default(),))
+}
+
+pub fn create_replay(world: &mut World, moves: Vec<SolverMove>) -> Entity {
+    world.spawn((Replay {
+        moves,
+        position: 0,
+        paused: false,
+        speed: DEFAULT_REPLAY_SPEED,
+        elapsed: Duration::ZERO,
+    },))
+}
+
+pub fn create_settings_menu(world: &mut World, themes: Vec<(String, String)>, selected: usize) -> Entity {
+    world.spawn((SettingsMenu { themes, selected },))
 }
 
 pub fn create_audio_store(world: &mut World) -> Entity {
     world.spawn((AudioStore::default(),))
 }
 
+pub fn create_image_store(world: &mut World) -> Entity {
+    world.spawn((ImageStore::default(),))
+}
+
+/// 使用指定主题，并预先加载地图中所有实体会用到的帧（包括动画的每一帧）
+/// 切换主题时也调用它，地图实体只记录图块种类，不需要重新创建
+pub fn load_images(world: &mut World, ctx: &mut ggez::Context, theme: &Theme) {
+    let tiles: HashSet<Tile> = world.query::<&Renderable>().iter().map(|(_, renderable)| renderable.tile).collect();
+    let mut image_store = world.query::<&mut ImageStore>();
+    let image_store = image_store.iter().next().unwrap().1;
+    image_store.set_theme(ctx, theme.clone());
+    for tile in tiles {
+        for frame in theme.frames(tile) {
+            image_store.sprite(ctx, frame);
+        }
+    }
+}
+
 pub fn load_sounds(world: &mut World,ctx: &mut ggez::Context){
     let mut audio_store = world.query::<&mut AudioStore>();
     let audio_store = audio_store.iter().next().unwrap().1;
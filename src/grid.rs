@@ -0,0 +1,102 @@
+use hecs::World;
+
+use crate::component::Position;
+
+/// 移动方向
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    /// 所有方向
+    pub const ALL: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+    /// 方向对应的坐标偏移 (dx, dy)
+    pub fn delta(self) -> (i32, i32) {
+        match self {
+            Direction::Up => (0, -1),
+            Direction::Down => (0, 1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+        }
+    }
+
+    /// 相反的方向
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+}
+
+/// 网格组件，由 `load_map` 根据关卡尺寸创建
+/// 提供带边界检查的坐标换算和相邻格子查询，越界一律返回 None，
+/// 调用方不需要自己处理坐标下溢或溢出
+/// 格子按 `y * width + x` 编号
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Grid {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Grid {
+    /// 创建指定尺寸的网格
+    pub fn new(width: usize, height: usize) -> Grid {
+        Grid { width, height }
+    }
+
+    /// 读取世界中的网格组件，没有时返回空网格
+    pub fn from_world(world: &World) -> Grid {
+        let mut query = world.query::<&Grid>();
+        query.iter().next().map(|(_, grid)| *grid).unwrap_or_default()
+    }
+
+    /// 格子数量
+    pub fn cell_count(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// 判断坐标是否在地图范围内
+    pub fn contains(&self, position: &Position) -> bool {
+        (position.x as usize) < self.width && (position.y as usize) < self.height
+    }
+
+    /// 坐标对应的格子编号，越界返回 None
+    pub fn index(&self, position: &Position) -> Option<usize> {
+        self.contains(position)
+            .then(|| position.y as usize * self.width + position.x as usize)
+    }
+
+    /// 格子编号对应的坐标
+    pub fn position(&self, cell: usize) -> Position {
+        Position {
+            x: (cell % self.width) as u8,
+            y: (cell / self.width) as u8,
+            z: 0,
+        }
+    }
+
+    /// 从格子沿指定方向移动一格后的格子编号，越界返回 None
+    pub fn step(&self, cell: usize, direction: Direction) -> Option<usize> {
+        let (dx, dy) = direction.delta();
+        let x = (cell % self.width).checked_add_signed(dx as isize)?;
+        let y = (cell / self.width).checked_add_signed(dy as isize)?;
+        (x < self.width && y < self.height).then_some(y * self.width + x)
+    }
+
+    /// 坐标沿指定方向的相邻坐标（保留 z），越界返回 None
+    pub fn neighbour(&self, position: &Position, direction: Direction) -> Option<Position> {
+        let cell = self.step(self.index(position)?, direction)?;
+        Some(Position {
+            z: position.z,
+            ..self.position(cell)
+        })
+    }
+}
@@ -0,0 +1,343 @@
+use std::{io::Read, path::PathBuf};
+
+use ggez::{GameError, GameResult};
+
+use crate::component::Ruleset;
+
+/// 关卡包所在的资源目录
+pub const LEVEL_PACK_DIR: &str = "/levels";
+
+/// 关卡包文件支持的扩展名
+const LEVEL_PACK_EXTENSIONS: [&str; 3] = ["txt", "sok", "xsb"];
+
+/// 单个关卡：标题、说明和地图字符串（内部格式或 XSB 格式）
+#[derive(Default)]
+pub struct Level {
+    pub title: String,
+    pub notes: Vec<String>,
+    /// 关卡指定的规则，未指定时使用关卡包或全局设置
+    pub ruleset: Option<Ruleset>,
+    pub map: String,
+}
+
+/// 关卡包：一个关卡文件中的所有关卡及其元信息
+#[derive(Default)]
+pub struct LevelPack {
+    /// 关卡包文件名（不含扩展名），用于生成存档和解法文件名
+    pub name: String,
+    pub title: String,
+    pub author: String,
+    pub description: String,
+    /// 关卡包指定的规则，未指定时使用全局设置
+    pub ruleset: Option<Ruleset>,
+    pub levels: Vec<Level>,
+}
+
+impl LevelPack {
+    /// 解析关卡包文本
+    /// `;` 开头的行为注释，`Key: value` 形式的行为元信息：
+    /// 第一个 `Level:` 之前的 Title / Author / Description / Ruleset 属于关卡包，
+    /// `Level:` 开始一个新关卡，其后的 `Note:` 为关卡说明，`Ruleset:` 为关卡规则。
+    /// 其余连续的非空行组成地图，没有 `Level:` 标题的地图块也会作为一个未命名关卡。
+    pub fn parse(default_title: &str, text: &str) -> LevelPack {
+        let mut pack = LevelPack {
+            name: default_title.to_string(),
+            title: default_title.to_string(),
+            ..Default::default()
+        };
+        let mut current: Option<Level> = None;
+        // 当前关卡的地图块是否已经结束（遇到空行）
+        let mut map_done = false;
+
+        for line in text.lines() {
+            let line = line.trim_end();
+            if line.trim_start().starts_with(';') {
+                continue;
+            }
+
+            if let Some((key, value)) = parse_meta(line) {
+                let value = value.to_string();
+                match key.as_str() {
+                    "level" => {
+                        pack.push_level(current.take());
+                        current = Some(Level {
+                            title: value,
+                            ..Default::default()
+                        });
+                        map_done = false;
+                    }
+                    "note" => current.get_or_insert_with(Level::default).notes.push(value),
+                    "ruleset" => match current.as_mut() {
+                        Some(level) => level.ruleset = Ruleset::parse(&value),
+                        None => pack.ruleset = Ruleset::parse(&value),
+                    },
+                    "title" if current.is_none() => pack.title = value,
+                    "author" if current.is_none() => pack.author = value,
+                    "description" if current.is_none() => pack.description = value,
+                    _ => (), // 未知的元信息，忽略
+                }
+                continue;
+            }
+
+            if line.trim().is_empty() {
+                if current.as_ref().is_some_and(|level| !level.map.is_empty()) {
+                    map_done = true;
+                }
+                continue;
+            }
+
+            // 地图行：上一个地图块已结束时开始一个新的未命名关卡
+            if map_done || current.is_none() {
+                pack.push_level(current.take());
+                current = Some(Level::default());
+                map_done = false;
+            }
+            let level = current.as_mut().unwrap();
+            level.map.push_str(line);
+            level.map.push('\n');
+        }
+        pack.push_level(current);
+
+        pack
+    }
+
+    /// 把关卡包写回文本格式，`parse` 可以原样读回
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (key, value) in [("Title", &self.title), ("Author", &self.author), ("Description", &self.description)] {
+            if !value.is_empty() {
+                text.push_str(&format!("{}: {}\n", key, value));
+            }
+        }
+        if let Some(ruleset) = self.ruleset {
+            text.push_str(&format!("Ruleset: {}\n", ruleset));
+        }
+
+        for level in self.levels.iter() {
+            text.push_str(&format!("\nLevel: {}\n", level.title));
+            if let Some(ruleset) = level.ruleset {
+                text.push_str(&format!("Ruleset: {}\n", ruleset));
+            }
+            for note in level.notes.iter() {
+                text.push_str(&format!("Note: {}\n", note));
+            }
+            text.push_str(&level.map);
+        }
+        text
+    }
+
+    /// 添加一个关卡，没有地图的关卡会被丢弃
+    fn push_level(&mut self, level: Option<Level>) {
+        if let Some(level) = level.filter(|level| !level.map.is_empty()) {
+            self.levels.push(level);
+        }
+    }
+}
+
+/// 解析 `Key: value` 形式的元信息行，返回小写的键和去掉空白的值
+/// 地图中不会出现 `:`，因此不会与地图行混淆
+fn parse_meta(line: &str) -> Option<(String, &str)> {
+    let (key, value) = line.split_once(':')?;
+    let key = key.trim();
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some((key.to_ascii_lowercase(), value.trim()))
+}
+
+/// 关卡库：所有已安装的关卡包
+/// 关卡包按文件名排序，其中的关卡按顺序统一编号
+#[derive(Default)]
+pub struct LevelLibrary {
+    pub packs: Vec<LevelPack>,
+    /// 全局关卡编号到 (关卡包下标, 包内关卡下标) 的映射
+    index: Vec<(usize, usize)>,
+}
+
+impl LevelLibrary {
+    /// 从资源目录加载所有关卡包
+    pub fn load(ctx: &ggez::Context) -> GameResult<LevelLibrary> {
+        let mut paths: Vec<PathBuf> = ctx
+            .fs
+            .read_dir(LEVEL_PACK_DIR)?
+            .filter(|path| {
+                path.extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| LEVEL_PACK_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+            })
+            .collect();
+        paths.sort();
+
+        let mut packs = Vec::new();
+        for path in paths {
+            let mut text = String::new();
+            ctx.fs.open(&path)?.read_to_string(&mut text)?;
+            let default_title = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            packs.push(LevelPack::parse(&default_title, &text));
+        }
+
+        let library = LevelLibrary::from_packs(packs);
+        if library.is_empty() {
+            return Err(GameError::ResourceLoadError(format!(
+                "no levels found under {}",
+                LEVEL_PACK_DIR
+            )));
+        }
+        Ok(library)
+    }
+
+    /// 由已解析的关卡包构建关卡库
+    pub fn from_packs(packs: Vec<LevelPack>) -> LevelLibrary {
+        let index = packs
+            .iter()
+            .enumerate()
+            .flat_map(|(pack_index, pack)| (0..pack.levels.len()).map(move |i| (pack_index, i)))
+            .collect();
+        LevelLibrary { packs, index }
+    }
+
+    /// 关卡总数
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// 是否没有任何关卡
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// 获取指定编号的关卡及其所在的关卡包
+    pub fn get(&self, level_index: usize) -> Option<(&LevelPack, &Level)> {
+        let &(pack_index, index) = self.index.get(level_index)?;
+        let pack = &self.packs[pack_index];
+        Some((pack, &pack.levels[index]))
+    }
+
+    /// 关卡实际使用的规则：关卡设置优先，其次是关卡包设置，最后是全局默认规则
+    pub fn ruleset(&self, level_index: usize, default: Ruleset) -> Ruleset {
+        self.get(level_index)
+            .and_then(|(pack, level)| level.ruleset.or(pack.ruleset))
+            .unwrap_or(default)
+    }
+
+    /// 关卡的唯一标识：关卡包文件名加包内编号，例如 `01_classic-003`
+    /// 新增关卡包不会改变已有关卡的标识，可用于存档和解法文件名
+    pub fn level_key(&self, level_index: usize) -> String {
+        match self.index.get(level_index) {
+            Some(&(pack_index, index)) => format!("{}-{:03}", self.packs[pack_index].name, index + 1),
+            None => String::new(),
+        }
+    }
+
+    /// 根据关卡标识查找关卡编号
+    pub fn find(&self, level_key: &str) -> Option<usize> {
+        (0..self.len()).find(|&level_index| self.level_key(level_index) == level_key)
+    }
+
+    /// 关卡的显示名称，没有标题时使用包内编号
+    pub fn display_name(&self, level_index: usize) -> String {
+        match self.index.get(level_index) {
+            Some(&(pack_index, index)) => {
+                let level = &self.packs[pack_index].levels[index];
+                if level.title.is_empty() {
+                    format!("Level {}", index + 1)
+                } else {
+                    level.title.clone()
+                }
+            }
+            None => String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LevelLibrary, LevelPack};
+    use crate::component::Ruleset;
+
+    const PACK: &str = "\
+; 注释行会被忽略
+Title: Test Pack
+Author: Someone
+Description: Two levels and an untitled map.
+Ruleset: classic
+
+Level: First
+Note: Push it.
+Note: Then push it again.
+#####
+#@$.#
+#####
+
+Level: Second
+Ruleset: multipush
+W W W W W
+W P BB BS W
+W W W W W
+
+#####
+#.$@#
+#####
+";
+
+    #[test]
+    fn pack_metadata() {
+        let pack = LevelPack::parse("test", PACK);
+        assert_eq!(pack.name, "test");
+        assert_eq!(pack.title, "Test Pack");
+        assert_eq!(pack.author, "Someone");
+        assert_eq!(pack.description, "Two levels and an untitled map.");
+        assert_eq!(pack.ruleset, Some(Ruleset::Classic));
+    }
+
+    #[test]
+    fn splits_levels_with_titles_notes_and_rulesets() {
+        let pack = LevelPack::parse("test", PACK);
+        assert_eq!(pack.levels.len(), 3);
+
+        let first = &pack.levels[0];
+        assert_eq!(first.title, "First");
+        assert_eq!(first.notes, vec!["Push it.", "Then push it again."]);
+        assert_eq!(first.ruleset, None);
+        assert_eq!(first.map, "#####\n#@$.#\n#####\n");
+
+        let second = &pack.levels[1];
+        assert_eq!(second.title, "Second");
+        assert_eq!(second.ruleset, Some(Ruleset::MultiPush));
+        assert_eq!(second.map.lines().count(), 3);
+
+        // 空行之后没有 `Level:` 标题的地图块是一个未命名关卡
+        let third = &pack.levels[2];
+        assert_eq!(third.title, "");
+        assert_eq!(third.map, "#####\n#.$@#\n#####\n");
+    }
+
+    #[test]
+    fn library_rulesets_keys_and_names() {
+        let library = LevelLibrary::from_packs(vec![LevelPack::parse("test", PACK)]);
+        assert_eq!(library.len(), 3);
+        // 关卡设置优先，其次是关卡包设置
+        assert_eq!(library.ruleset(0, Ruleset::MultiPush), Ruleset::Classic);
+        assert_eq!(library.ruleset(1, Ruleset::Classic), Ruleset::MultiPush);
+        assert_eq!(library.level_key(2), "test-003");
+        assert_eq!(library.find("test-002"), Some(1));
+        assert_eq!(library.display_name(2), "Level 3");
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let pack = LevelPack::parse("test", PACK);
+        let reparsed = LevelPack::parse("test", &pack.to_text());
+        assert_eq!(reparsed.title, pack.title);
+        assert_eq!(reparsed.ruleset, pack.ruleset);
+        assert_eq!(reparsed.levels.len(), pack.levels.len());
+        for (reparsed, level) in reparsed.levels.iter().zip(pack.levels.iter()) {
+            assert_eq!(reparsed.notes, level.notes);
+            assert_eq!(reparsed.ruleset, level.ruleset);
+            assert_eq!(reparsed.map, level.map);
+        }
+    }
+}
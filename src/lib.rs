@@ -0,0 +1,22 @@
+//! 推箱子游戏的核心库
+//! 游戏窗口（`rust-sokoban`）和命令行工具（`sokoban-cli`）共用这些模块
+
+pub mod atlas;
+pub mod bindings;
+pub mod entity;
+pub mod component;
+pub mod constants;
+pub mod deadlock;
+pub mod grid;
+pub mod level_pack;
+pub mod map;
+pub mod progress;
+pub mod savegame;
+pub mod simulation;
+pub mod solution;
+pub mod solver;
+pub mod tool;
+pub mod systems;
+pub mod settings;
+pub mod theme;
+pub mod validate;
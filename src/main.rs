@@ -3,14 +3,7 @@ use std::path;
 use ggez::{GameResult, conf, event};
 use hecs::{ World};
 
-use crate::{map::initialize_level, tool::Game};
-
-mod entity;
-mod component;
-mod constants;
-mod map;
-mod tool;
-mod systems;
+use rust_sokoban::{map::initialize_level, tool::Game};
 
 /// 游戏入口函数
 /// 初始化游戏上下文、窗口设置和事件循环
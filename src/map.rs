@@ -1,349 +1,130 @@
+use std::{collections::HashSet, fmt::Display};
+
 use hecs::World;
 
-use crate::{component::{BoxColor, Position}, entity::{self, create_box, create_box_spot, create_floor, create_player, create_wall, load_sounds}};
-
-/// 关卡地图数据数组
-/// 每个字符串代表一个关卡的布局
-/// N: 空白, W: 墙, P: 玩家, .: 地板
-/// BB: 蓝箱子, RB: 红箱子, BS: 蓝目标点, RS: 红目标点
-pub const LEVELS: [&str; 30] = [
-    // Level 1: 入门 - 单箱推动
-    "
-    N N W W W W W N
-    W W W . . . W N
-    W . P . BB . W N
-    W . . . . BS W N
-    W . . . . . W N
-    W W W W W W W N
-    ",
-    // Level 2: 入门 - 双箱顺序
-    "
-    N N W W W W W W
-    W W W . . . . W
-    W . . . BB . . W
-    W . . BB . . . W 
-    W . P . . . . W
-    W . . . . BS . W
-    W . . BS . . . W
-    W . . . . . . W
-    W W W W W W W W
-    ",
-    // Level 3: 基础 - 转角技巧
-    "
-    N N W W W W W N
-    W W W . . . W N
-    W . P . BB . W N
-    W . . . . . W N
-    W W . BB . W W
-    W . . . . BS W
-    W . . . BS . W
-    W W W W W W W
-    ",
-    // Level 4: 基础 - 走廊
-    "
-    W W W W W W W W
-    W . . . . . . W
-    W . BB . BB . W
-    W . . P . . . W
-    W . BS . BS . W
-    W . . . . . . W
-    W W W W W W W W
-    ",
-    // Level 5: 初级 - T字型
-    "
-    N W W W W W N
-    W W . . . W W
-    W . . BB . . W
-    W . . BB . . W
-    W W . BS . W W
-    N W . BS . W N
-    N W . P . W N
-    N W W W W W N
-    ",
-    // Level 6: 初级 - 经典的 Microban 1
-    "
-    W W W W W N N
-    W . . . W N N
-    W . BS . W N N
-    W W BB . W W W
-    W . . BB . . W
-    W . . P W . W
-    W W BS . . . W
-    N W W W W W W
-    ",
-    // Level 7: 初级 - 狭窄空间
-    "
-    N N W W W W N
-    W W W . . W N
-    W BS P BB . W N
-    W W W . BB W N
-    N W BS . . W N
-    N W . . W W N
-    N W W W W N N
-    ",
-    // Level 8: 初级 - 需要规划
-    "
-    W W W W W W W W
-    W . . . . . . W
-    W . P BB BB . W
-    W . . . . . . W
-    W . BS BS . . W
-    W . . . . . . W
-    W W W W W W W W
-    ",
-    // Level 9: 中级 - 障碍物
-    "
-    N W W W W W N
-    W W . . . W W
-    W . . BB . . W
-    W . W BB W . W
-    W . . P . . W
-    W . BS BS . W
-    W W . . . W W
-    N W W W W W N
-    ",
-    // Level 10: 中级 - 经典的 U 型
-    "
-    N W W W W W N
-    N W . . . W N
-    N W . BB . W N
-    W W . . . W W
-    W . . P . . W
-    W . BB . BB . W
-    W BS . . . BS W
-    W W W W W W W
-    ",
-    // Level 11
-    "
-    W W W W W W N
-    W BS . . . W N
-    W . BB P . W N
-    W W W . . W W
-    N W . BB . . W
-    N W . . . BS W
-    N W W W W W W
-    ",
-    // Level 12
-    "
-    N W W W W W N
-    W W . . . W N
-    W . . BB . W W
-    W . W . W . W
-    W . P . . . W
-    W W . BB . W W
-    N W BS BS W W N
-    N W W W W W N N
-    ",
-    // Level 13
-    "
-    W W W W W W W
-    W BS . . . BS W
-    W . BB . BB . W
-    W . . P . . W
-    W . . . . . W
-    W W W W W W W
-    ",
-    // Level 14
-    "
-    N N W W W N N
-    N N W BS W N N
-    W W W . W W W
-    W . . BB . . W
-    W . P . . . W
-    W W W . W W W
-    N N W BS W N N
-    N N W W W N N
-    ",
-    // Level 15
-    "
-    W W W W W W W
-    W . . . . . W
-    W . BS BB BS . W
-    W . . P . . W
-    W . BS BB BS . W
-    W . . . . . W
-    W W W W W W W
-    ",
-    // Level 16: 进阶 - 拥挤
-    "
-    N W W W W N N
-    W W . . W W N
-    W . . BB . W N
-    W . BB BS . W N
-    W W P BS . W N
-    N W . . W W N
-    N W W W W N N
-    ",
-    // Level 17
-    "
-    W W W W W W N
-    W . . . . W N
-    W . BB BB . W N
-    W . P . . W W
-    W W . BS BS . W
-    N W . . . . W
-    N W W W W W W
-    ",
-    // Level 18
-    "
-    N N W W W W N
-    W W W . . W N
-    W . . . BB W N
-    W . P BB . W N
-    W W W BS BS W N
-    N N W . . W N
-    N N W W W W N
-    ",
-    // Level 19
-    "
-    N W W W W W N
-    N W . . . W N
-    W W . BB . W W
-    W . . BS . . W
-    W . BS P BS . W
-    W . . BB . . W
-    W W . . . W W
-    N W W W W W N
-    ",
-    // Level 20
-    "
-    W W W W W W W
-    W . BS BS BS . W
-    W . . . . . W
-    W . BB BB BB . W
-    W . . P . . W
-    W . . . . . W
-    W W W W W W W
-    ",
-    // Level 21: 高级 - 复杂地形
-    "
-    N N W W W N N
-    N W W . W W N
-    W W . . . W W
-    W . . BB . . W
-    W . BB P BB . W
-    W . . BB . . W
-    W W . . . W W
-    N W BS BS BS W N
-    N W W W W W N
-    ",
-    // Level 22
-    "
-    W W W W W W W
-    W BS . . . BS W
-    W . . W . . W
-    W . BB P BB . W
-    W . . W . . W
-    W BS . . . BS W
-    W W W W W W W
-    ",
-    // Level 23
-    "
-    N W W W W W N
-    W W . . . W W
-    W . . BB . . W
-    W . BB . BB . W
-    W . . P . . W
-    W . BS BS BS . W
-    W W . . . W W
-    N W W W W W N
-    ",
-    // Level 24
-    "
-    N N W W W N N
-    N W W . W W N
-    W W . . . W W
-    W . BB . BB . W
-    W . BS P BS . W
-    W . BB . BB . W
-    W W . . . W W
-    N W W W W W N
-    ",
-    // Level 25
-    "
-    W W W W W W W
-    W . . . . . W
-    W . BS . BS . W
-    W . . BB . . W
-    W . BB P BB . W
-    W . . . . . W
-    W W W W W W W
-    ",
-    // Level 26
-    "
-    N N W W W N N
-    N W W . W W N
-    W W . . . W W
-    W . . BB . . W
-    W . BS P BS . W
-    W . . BB . . W
-    W W . . . W W
-    N W W W W W N
-    ",
-    // Level 27
-    "
-    W W W W W W W
-    W . . . . . W
-    W . BS . BS . W
-    W . BB . BB . W
-    W . . P . . W
-    W . . . . . W
-    W W W W W W W
-    ",
-    // Level 28
-    "
-    N W W W W W N
-    W W . . . W W
-    W . . BB . . W
-    W . BB P BB . W
-    W . . BB . . W
-    W . BS . BS . W
-    W W . . . W W
-    N W W W W W N
-    ",
-    // Level 29
-    "
-    W W W W W W W
-    W . . . . . W
-    W . BS . BS . W
-    W . BB P BB . W
-    W . BS . BS . W
-    W . . . . . W
-    W W W W W W W
-    ",
-    // Level 30: 最终挑战
-    "
-    N N W W W N N
-    N W W . W W N
-    W W . . . W W
-    W . BB BB BB . W
-    W . BS BS BS . W
-    W . . P . . W
-    W W . . . W W
-    N W W W W W N
-    "
-];
+use crate::{component::{Box, BoxColor, BoxSpot, Player, Position, Ruleset, Wall}, grid::Grid, level_pack::LevelLibrary, theme::Theme, entity::{self, create_box, create_box_spot, create_floor, create_player, create_wall, load_images, load_sounds}, validate::{describe_position, validate_world, LevelProblem}};
 
 /// 初始化指定关卡
 /// 清除旧实体并加载新地图和资源
-pub fn initialize_level(world: &mut World, ctx: &mut ggez::Context, level_index: usize) {
-    if level_index < LEVELS.len() {
+/// 地图有误时不创建任何地图实体，改为记录 `MapError`，由界面显示错误信息
+pub fn initialize_level(world: &mut World, ctx: &mut ggez::Context, library: &LevelLibrary, level_index: usize, default_ruleset: Ruleset, theme: &Theme) {
+    if let Some((_, level)) = library.get(level_index) {
         // 创建核心游戏系统实体
         entity::create_gameplay(world);
         entity::create_time(world);
-        entity::create_event_queue(world);
+        entity::create_history(world);
+        entity::create_solver_status(world);
+        entity::create_hint(world);
+        entity::create_search(world);
+        entity::create_key_repeat(world);
+        entity::create_click_move(world);
+        entity::create_image_store(world);
         entity::create_audio_store(world);
-        // 加载地图
-        load_map(world, LEVELS[level_index].to_string());
+        // 加载地图（自动识别内部格式或 XSB 格式）
+        if let Err(error) = load_level_map(world, level.map.clone()) {
+            clear_map(world);
+            entity::create_map_error(world, error);
+        }
+        // 记录本关使用的规则，死锁检测和求解器都依赖它
+        entity::create_ruleset(world, library.ruleset(level_index, default_ruleset));
+        // 预先计算死格，用于死锁检测
+        entity::create_deadlock_analysis(world);
+        // 按当前主题预先加载地图用到的图片，渲染时不再读取文件
+        load_images(world, ctx, theme);
         // 加载音频资源
         load_sounds(world, ctx);
     }
 }
 
-/// 解析地图字符串并创建相应的实体
-pub fn load_map(world: &mut World, map_string: String) {
+/// 删除所有地图实体（带有位置的实体），保留游戏系统实体
+fn clear_map(world: &mut World) {
+    let entities: Vec<_> = world.query::<&Position>().iter().map(|(entity, _)| entity).collect();
+    for entity in entities {
+        let _ = world.despawn(entity);
+    }
+}
+
+/// 地图的最大宽度和高度：格子坐标以 u8 保存
+pub const MAX_MAP_SIZE: usize = u8::MAX as usize + 1;
+
+/// 地图加载失败的原因
+#[derive(PartialEq, Eq)]
+pub enum MapError {
+    /// 无法识别的地图记号（内部格式中以空格分隔的记号，或 XSB 中的单个字符）
+    UnknownToken { position: Position, token: String },
+    /// 地图的宽度或高度超过 `MAX_MAP_SIZE`，格子坐标无法表示
+    TooLarge { width: usize, height: usize },
+    /// 地图可以解析，但关卡本身不合法（校验发现的所有问题）
+    Invalid(Vec<LevelProblem>),
+}
+
+impl MapError {
+    /// 每个问题一行的错误描述，用于错误界面
+    pub fn messages(&self) -> Vec<String> {
+        match self {
+            MapError::UnknownToken { position, token } => {
+                vec![format!("unrecognized map item '{}' at {}", token, describe_position(position))]
+            }
+            MapError::TooLarge { width, height } => {
+                vec![format!("map is {}x{}, larger than the {}x{} maximum", width, height, MAX_MAP_SIZE, MAX_MAP_SIZE)]
+            }
+            MapError::Invalid(problems) => problems.iter().map(|problem| problem.to_string()).collect(),
+        }
+    }
+}
+
+/// 创建地图网格，尺寸超过 `MAX_MAP_SIZE` 时报错（此时不创建任何实体）
+fn create_checked_grid(world: &mut World, width: usize, height: usize) -> Result<(), MapError> {
+    if width > MAX_MAP_SIZE || height > MAX_MAP_SIZE {
+        return Err(MapError::TooLarge { width, height });
+    }
+    entity::create_grid(world, width, height);
+    Ok(())
+}
+
+impl Display for MapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.messages().join("; "))
+    }
+}
+
+/// XSB 格式没有颜色信息，所有箱子和目标点统一使用该颜色
+const XSB_DEFAULT_COLOR: BoxColor = BoxColor::Blue;
+
+/// 根据地图字符串的格式选择对应的解析函数，解析后校验关卡
+/// 出错时世界中可能留下部分地图实体，由调用方清理
+pub fn load_level_map(world: &mut World, map_string: String) -> Result<(), MapError> {
+    if is_xsb(&map_string) {
+        load_xsb_map(world, map_string)?;
+    } else {
+        load_map(world, map_string)?;
+    }
+
+    let problems = validate_world(world);
+    if !problems.is_empty() {
+        return Err(MapError::Invalid(problems));
+    }
+    Ok(())
+}
+
+/// 判断地图字符串是否为标准 XSB 格式
+/// 内部格式只使用字母和 `.`，出现 XSB 专有字符即视为 XSB
+pub fn is_xsb(map_string: &str) -> bool {
+    map_string
+        .chars()
+        .any(|c| matches!(c, '#' | '@' | '+' | '$' | '*' | '-' | '_'))
+}
+
+/// 解析内部格式的地图字符串并创建相应的实体
+/// N: 空白, W: 墙, P: 玩家, .: 地板
+/// BB: 蓝箱子, RB: 红箱子, BS: 蓝目标点, RS: 红目标点
+pub fn load_map(world: &mut World, map_string: String) -> Result<(), MapError> {
     // 按行分割地图字符串
     let rows: Vec<&str> = map_string.trim().split('\n').map(|s| s.trim()).collect();
+    // 根据地图尺寸创建网格（最长的一行为宽度）
+    let width = rows.iter().map(|row| row.split(' ').count()).max().unwrap_or(0);
+    create_checked_grid(world, width, rows.len())?;
+
     for (y, row) in rows.iter().enumerate() {
         // 按空格分割每一行
         let columns: Vec<&str> = row.split(' ').collect();
@@ -384,8 +165,374 @@ pub fn load_map(world: &mut World, map_string: String) {
                     create_box_spot(world, &position, BoxColor::Red);
                 }
                 "N" => (), // 空白区域，不做任何操作
-                c => panic!("unrecognized map item {}", c), // 未知字符报错
+                token => {
+                    // 未知记号报错
+                    return Err(MapError::UnknownToken { position, token: token.to_string() });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 解析标准 XSB 格式的地图字符串并创建相应的实体
+/// #: 墙, @: 玩家, +: 站在目标点上的玩家, $: 箱子, *: 在目标点上的箱子
+/// .: 目标点, 空格 / - / _: 地板
+/// 支持游程编码：字符前的数字表示重复次数（例如 `4#` 即 `####`），`|` 与换行一样分隔行
+pub fn load_xsb_map(world: &mut World, map_string: String) -> Result<(), MapError> {
+    let map_string = expand_run_length(&map_string);
+    // 去掉首尾空行和行尾空白，保留行首空格（XSB 中行首空格是有意义的）
+    let rows: Vec<&str> = map_string
+        .lines()
+        .map(|row| row.trim_end())
+        .skip_while(|row| row.is_empty())
+        .collect();
+    let rows = &rows[..rows.iter().rposition(|row| !row.is_empty()).map_or(0, |i| i + 1)];
+
+    // 去掉所有行共同的缩进（例如写在 Rust 字符串字面量中的关卡）
+    let indent = rows
+        .iter()
+        .filter(|row| !row.is_empty())
+        .map(|row| row.len() - row.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let grid: Vec<Vec<char>> = rows
+        .iter()
+        .map(|row| row.chars().skip(indent).collect())
+        .collect();
+
+    // 空格既可能是地板也可能是墙外的空白，
+    // 从玩家位置出发做洪水填充，只有能到达的格子才铺地板
+    let inside = xsb_interior(&grid);
+
+    // 根据地图尺寸创建网格（最长的一行为宽度）
+    let width = grid.iter().map(|row| row.len()).max().unwrap_or(0);
+    create_checked_grid(world, width, grid.len())?;
+
+    for (y, row) in grid.iter().enumerate() {
+        for (x, item) in row.iter().enumerate() {
+            let position = Position {
+                x: x as u8,
+                y: y as u8,
+                z: 0,
+            };
+
+            // 根据字符创建对应实体
+            match *item {
+                ' ' | '-' | '_' => {
+                    if inside.contains(&(x, y)) {
+                        create_floor(world, &position);
+                    }
+                }
+                '#' => {
+                    create_floor(world, &position);
+                    create_wall(world, &position);
+                }
+                '@' => {
+                    create_floor(world, &position);
+                    create_player(world, &position);
+                }
+                '+' => {
+                    create_floor(world, &position);
+                    create_box_spot(world, &position, XSB_DEFAULT_COLOR);
+                    create_player(world, &position);
+                }
+                '$' => {
+                    create_floor(world, &position);
+                    create_box(world, &position, XSB_DEFAULT_COLOR);
+                }
+                '*' => {
+                    create_floor(world, &position);
+                    create_box_spot(world, &position, XSB_DEFAULT_COLOR);
+                    create_box(world, &position, XSB_DEFAULT_COLOR);
+                }
+                '.' => {
+                    create_floor(world, &position);
+                    create_box_spot(world, &position, XSB_DEFAULT_COLOR);
+                }
+                c => {
+                    // 未知字符报错（列号不含去掉的公共缩进）
+                    return Err(MapError::UnknownToken { position, token: c.to_string() });
+                }
             }
         }
     }
+    Ok(())
+}
+
+/// 展开 XSB 的游程编码，没有编码的地图原样返回
+fn expand_run_length(map_string: &str) -> String {
+    let mut expanded = String::with_capacity(map_string.len());
+    let mut count: Option<usize> = None;
+    for c in map_string.chars() {
+        match c {
+            '0'..='9' => {
+                let digit = c.to_digit(10).unwrap() as usize;
+                count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+            }
+            '|' => expanded.push('\n'),
+            // 超过最大尺寸的行反正会被拒绝，不必展开得更长
+            c => expanded.extend(std::iter::repeat_n(c, count.unwrap_or(1).min(MAX_MAP_SIZE + 1))),
+        }
+        if !c.is_ascii_digit() {
+            count = None;
+        }
+    }
+    expanded
+}
+
+/// 计算 XSB 地图中玩家可以到达的区域（不穿墙），用于区分地板和墙外空白
+fn xsb_interior(grid: &[Vec<char>]) -> HashSet<(usize, usize)> {
+    let mut inside = HashSet::new();
+    let mut stack: Vec<(usize, usize)> = grid
+        .iter()
+        .enumerate()
+        .flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, c)| matches!(c, '@' | '+'))
+                .map(move |(x, _)| (x, y))
+        })
+        .collect();
+
+    while let Some((x, y)) = stack.pop() {
+        match grid.get(y).and_then(|row| row.get(x)) {
+            None | Some('#') => continue,
+            Some(_) => {}
+        }
+        if !inside.insert((x, y)) {
+            continue;
+        }
+        stack.push((x + 1, y));
+        stack.push((x, y + 1));
+        if x > 0 {
+            stack.push((x - 1, y));
+        }
+        if y > 0 {
+            stack.push((x, y - 1));
+        }
+    }
+
+    inside
+}
+
+/// 地图无法转换为目标格式的原因
+#[derive(PartialEq, Eq)]
+pub enum ConvertError {
+    /// 内部格式每个格子只有一个记号，无法表示站在目标点上的玩家或箱子
+    Stacked { position: Position },
+    /// XSB 格式没有颜色，无法表示非默认颜色的箱子或目标点
+    Colored { position: Position, color: BoxColor },
+}
+
+impl Display for ConvertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConvertError::Stacked { position } => write!(
+                f,
+                "the internal format cannot place a player or box on a goal ({})",
+                describe_position(position)
+            ),
+            ConvertError::Colored { position, color } => write!(
+                f,
+                "XSB has no colors, found a {} box or goal at {}",
+                color,
+                describe_position(position)
+            ),
+        }
+    }
+}
+
+/// 导出地图时单个格子上的内容
+#[derive(Clone, Copy, Default)]
+struct MapCell {
+    /// 格子上是否有任何地图实体（没有即为空白）
+    occupied: bool,
+    wall: bool,
+    player: bool,
+    the_box: Option<BoxColor>,
+    goal: Option<BoxColor>,
+}
+
+/// 读取世界中的地图，按行返回每个格子的内容
+fn map_cells(world: &World) -> Vec<Vec<MapCell>> {
+    let grid = Grid::from_world(world);
+    let mut cells = vec![MapCell::default(); grid.cell_count()];
+    for (_, position) in world.query::<&Position>().iter() {
+        if let Some(cell) = grid.index(position) {
+            cells[cell].occupied = true;
+        }
+    }
+    for (_, (position, _)) in world.query::<(&Position, &Wall)>().iter() {
+        if let Some(cell) = grid.index(position) {
+            cells[cell].wall = true;
+        }
+    }
+    for (_, (position, _)) in world.query::<(&Position, &Player)>().iter() {
+        if let Some(cell) = grid.index(position) {
+            cells[cell].player = true;
+        }
+    }
+    for (_, (position, the_box)) in world.query::<(&Position, &Box)>().iter() {
+        if let Some(cell) = grid.index(position) {
+            cells[cell].the_box = Some(the_box.color);
+        }
+    }
+    for (_, (position, box_spot)) in world.query::<(&Position, &BoxSpot)>().iter() {
+        if let Some(cell) = grid.index(position) {
+            cells[cell].goal = Some(box_spot.color);
+        }
+    }
+
+    if grid.width == 0 {
+        return Vec::new();
+    }
+    cells.chunks(grid.width).map(|row| row.to_vec()).collect()
+}
+
+/// 把世界中的地图导出为内部格式的地图字符串
+pub fn export_map(world: &World) -> Result<String, ConvertError> {
+    let mut map = String::new();
+    for (y, row) in map_cells(world).iter().enumerate() {
+        let mut tokens = Vec::with_capacity(row.len());
+        for (x, cell) in row.iter().enumerate() {
+            let position = Position { x: x as u8, y: y as u8, z: 0 };
+            let token = match (cell.wall, cell.player, cell.the_box, cell.goal) {
+                (true, ..) => "W",
+                (_, true, None, None) => "P",
+                (_, false, Some(BoxColor::Blue), None) => "BB",
+                (_, false, Some(BoxColor::Red), None) => "RB",
+                (_, false, None, Some(BoxColor::Blue)) => "BS",
+                (_, false, None, Some(BoxColor::Red)) => "RS",
+                (_, false, None, None) if cell.occupied => ".",
+                (_, false, None, None) => "N",
+                _ => return Err(ConvertError::Stacked { position }),
+            };
+            tokens.push(token);
+        }
+        map.push_str(&tokens.join(" "));
+        map.push('\n');
+    }
+    Ok(map)
+}
+
+/// 把世界中的地图导出为标准 XSB 格式的地图字符串
+/// XSB 没有颜色，只能导出全部为默认颜色的关卡
+pub fn export_xsb_map(world: &World) -> Result<String, ConvertError> {
+    let mut map = String::new();
+    for (y, row) in map_cells(world).iter().enumerate() {
+        let mut line = String::with_capacity(row.len());
+        for (x, cell) in row.iter().enumerate() {
+            let position = Position { x: x as u8, y: y as u8, z: 0 };
+            if let Some(&color) = [cell.the_box, cell.goal].iter().flatten().find(|&&color| color != XSB_DEFAULT_COLOR) {
+                return Err(ConvertError::Colored { position, color });
+            }
+            line.push(match (cell.wall, cell.player, cell.the_box.is_some(), cell.goal.is_some()) {
+                (true, ..) => '#',
+                (_, true, _, true) => '+',
+                (_, true, _, false) => '@',
+                (_, _, true, true) => '*',
+                (_, _, true, false) => '$',
+                (_, _, false, true) => '.',
+                _ => ' ',
+            });
+        }
+        map.push_str(line.trim_end());
+        map.push('\n');
+    }
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use hecs::World;
+
+    use super::{export_xsb_map, load_map, load_xsb_map, MapError, MAX_MAP_SIZE};
+    use crate::component::{Box, BoxSpot, Player, Position};
+
+    fn xsb_world(map: &str) -> World {
+        let mut world = World::new();
+        assert!(load_xsb_map(&mut world, map.to_string()).is_ok(), "invalid test map");
+        world
+    }
+
+    fn positions<T: hecs::Component>(world: &World) -> Vec<(u8, u8)> {
+        let mut positions: Vec<(u8, u8)> = world
+            .query::<(&Position, &T)>()
+            .iter()
+            .map(|(_, (position, _))| (position.x, position.y))
+            .collect();
+        positions.sort();
+        positions
+    }
+
+    #[test]
+    fn player_and_box_on_goal() {
+        let world = xsb_world("
+            ######
+            #+ *$#
+            #-.  #
+            ######
+        ");
+        // `+` 是站在目标点上的玩家，`*` 是在目标点上的箱子
+        assert_eq!(positions::<Player>(&world), vec![(1, 1)]);
+        assert_eq!(positions::<Box>(&world), vec![(3, 1), (4, 1)]);
+        assert_eq!(positions::<BoxSpot>(&world), vec![(1, 1), (2, 2), (3, 1)]);
+        assert_eq!(export_xsb_map(&world).ok().as_deref(), Some("######\n#+ *$#\n# .  #\n######\n"));
+    }
+
+    #[test]
+    fn spaces_outside_the_walls_are_not_floor() {
+        let world = xsb_world("
+              ####
+            ###@.#
+            #$   #
+            ######
+        ");
+        // 左上角的空白在墙外，没有任何实体
+        assert!(world.query::<&Position>().iter().all(|(_, position)| (position.x, position.y) != (0, 0)));
+        assert_eq!(positions::<Box>(&world), vec![(1, 2)]);
+    }
+
+    #[test]
+    fn run_length_encoding() {
+        let plain = xsb_world("
+            ######
+            #@$ .#
+            ######
+        ");
+        let encoded = xsb_world("6#|#@$-.#|6#");
+        assert_eq!(export_xsb_map(&encoded).ok(), export_xsb_map(&plain).ok());
+
+        // 多位数和每行分开写也可以
+        let encoded = xsb_world("12#\n#@3-$4-.#\n12#");
+        assert_eq!(export_xsb_map(&encoded).ok().as_deref(), Some("############\n#@   $    .#\n############\n"));
+    }
+
+    #[test]
+    fn oversized_maps_are_rejected() {
+        let mut world = World::new();
+        let result = load_xsb_map(&mut world, format!("{}#|#@$.#|4#", MAX_MAP_SIZE + 1));
+        assert!(matches!(result, Err(MapError::TooLarge { width: 257, height: 3 })));
+        assert_eq!(world.len(), 0);
+
+        let mut world = World::new();
+        let tall = vec!["W"; MAX_MAP_SIZE + 1].join("\n");
+        assert!(matches!(load_map(&mut world, tall), Err(MapError::TooLarge { width: 1, height: 257 })));
+
+        // 正好是最大尺寸的地图可以加载，最后一格的坐标没有回绕
+        let mut world = World::new();
+        assert!(load_xsb_map(&mut world, format!("{}#|#@$.{}-#|{}#", MAX_MAP_SIZE, MAX_MAP_SIZE - 5, MAX_MAP_SIZE)).is_ok());
+        assert!(world.query::<&Position>().iter().any(|(_, position)| position.x == u8::MAX));
+    }
+
+    #[test]
+    fn unknown_character_is_an_error() {
+        let mut world = World::new();
+        let result = load_xsb_map(&mut world, "####\n#@x#\n####".to_string());
+        assert!(matches!(
+            result,
+            Err(MapError::UnknownToken { position: Position { x: 2, y: 1, .. }, ref token }) if token == "x"
+        ));
+    }
 }
@@ -0,0 +1,122 @@
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use ggez::{GameError, GameResult};
+use serde::{Deserialize, Serialize};
+
+use crate::component::GamePlay;
+
+/// 存档文件名（位于用户数据目录下）
+pub const PROGRESS_FILE: &str = "progress.toml";
+
+/// 一次通关的成绩
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Score {
+    pub moves: u32,
+    pub pushes: u32,
+    pub box_lines: u32,
+    pub player_lines: u32,
+    /// 通关的解法（LURD）
+    pub solution: String,
+}
+
+impl Score {
+    /// 读取通关时的统计和移动记录
+    pub fn from_gameplay(gameplay: &GamePlay) -> Score {
+        Score {
+            moves: gameplay.move_count,
+            pushes: gameplay.push_count,
+            box_lines: gameplay.box_lines,
+            player_lines: gameplay.player_lines,
+            solution: gameplay.move_log.clone(),
+        }
+    }
+
+    /// 按步数排名的比较键：步数优先，其次是推动次数和直线段数
+    fn move_rank(&self) -> (u32, u32, u32, u32) {
+        (self.moves, self.pushes, self.box_lines, self.player_lines)
+    }
+
+    /// 按推动次数排名的比较键：推动次数优先，其次是步数和直线段数
+    fn push_rank(&self) -> (u32, u32, u32, u32) {
+        (self.pushes, self.moves, self.box_lines, self.player_lines)
+    }
+}
+
+/// 单个关卡的最佳记录，分别保存步数最优和推动次数最优的成绩
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct LevelRecord {
+    pub best_moves: Score,
+    pub best_pushes: Score,
+}
+
+/// 玩家进度：已通关的关卡及其最佳记录，以及上次游玩的关卡
+/// 关卡以 `LevelLibrary::level_key` 为键，新增关卡包不会打乱已有记录
+#[derive(Default, Serialize, Deserialize)]
+pub struct Progress {
+    /// 上次游玩的关卡
+    #[serde(default)]
+    pub current_level: String,
+    #[serde(default)]
+    pub levels: BTreeMap<String, LevelRecord>,
+}
+
+impl Progress {
+    /// 存档文件路径
+    pub fn path(ctx: &ggez::Context) -> PathBuf {
+        ctx.fs.user_data_dir().join(PROGRESS_FILE)
+    }
+
+    /// 读取存档，没有存档或存档损坏时返回空进度
+    pub fn load(ctx: &ggez::Context) -> Progress {
+        let path = Progress::path(ctx);
+        let Ok(text) = fs::read_to_string(&path) else {
+            return Progress::default();
+        };
+        toml::from_str(&text).unwrap_or_else(|error| {
+            println!("ignoring corrupt progress file {}: {}", path.display(), error);
+            Progress::default()
+        })
+    }
+
+    /// 写入存档
+    pub fn save(&self, ctx: &ggez::Context) -> GameResult {
+        let path = Progress::path(ctx);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(self).map_err(|error| GameError::CustomError(error.to_string()))?;
+        fs::write(&path, text)?;
+        Ok(())
+    }
+
+    /// 关卡的最佳记录，没有通关时返回 None
+    pub fn get(&self, level_key: &str) -> Option<&LevelRecord> {
+        self.levels.get(level_key)
+    }
+
+    /// 记录一次通关，返回是否刷新了任意一项记录
+    pub fn record(&mut self, level_key: &str, score: Score) -> bool {
+        let Some(record) = self.levels.get_mut(level_key) else {
+            self.levels.insert(
+                level_key.to_string(),
+                LevelRecord {
+                    best_moves: score.clone(),
+                    best_pushes: score,
+                },
+            );
+            return true;
+        };
+
+        let mut improved = false;
+        if score.move_rank() < record.best_moves.move_rank() {
+            record.best_moves = score.clone();
+            improved = true;
+        }
+        if score.push_rank() < record.best_pushes.push_rank() {
+            record.best_pushes = score;
+            improved = true;
+        }
+        improved
+    }
+}
+
@@ -0,0 +1,156 @@
+use std::{fs, path::PathBuf};
+
+use ggez::{GameError, GameResult};
+use hecs::World;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    component::{Box, BoxColor, GamePlay, GameplayState, History, Player, Position, Ruleset},
+    simulation::{apply_action, player_moved},
+    solver::SolverMove,
+    systems::history::undo,
+};
+
+/// 未完成关卡的存档文件名（位于用户数据目录下）
+pub const SAVEGAME_FILE: &str = "savegame.toml";
+
+/// 存档中的箱子
+/// TOML 要求普通字段写在表之前，因此 `color` 在 `position` 之前
+#[derive(Serialize, Deserialize)]
+pub struct SavedBox {
+    pub color: BoxColor,
+    pub position: Position,
+}
+
+/// 未完成关卡的存档：关闭窗口时写入，下次启动时恢复
+/// 恢复时在重新加载的关卡上重放移动记录，以便重建撤销/重做历史；
+/// 重放结果与保存的局面不符（例如关卡文件被修改）时放弃存档
+#[derive(Serialize, Deserialize)]
+pub struct SavedGame {
+    /// 关卡标识（`LevelLibrary::level_key`）
+    pub level: String,
+    /// 保存时的全局默认规则
+    pub ruleset: Ruleset,
+    pub move_count: u32,
+    /// 已执行的移动（LURD）
+    pub move_log: String,
+    /// 被撤销、尚可重做的移动（LURD），按重做的顺序排列
+    pub redo_log: String,
+    pub player: Position,
+    pub boxes: Vec<SavedBox>,
+}
+
+impl SavedGame {
+    /// 存档文件路径
+    pub fn path(ctx: &ggez::Context) -> PathBuf {
+        ctx.fs.user_data_dir().join(SAVEGAME_FILE)
+    }
+
+    /// 记录当前局面，关卡还没开始或已经通关时返回 None
+    pub fn capture(world: &World, level: String, ruleset: Ruleset) -> Option<SavedGame> {
+        let (move_count, move_log, state) = {
+            let mut query = world.query::<&GamePlay>();
+            let gameplay = query.iter().next()?.1;
+            (gameplay.move_count, gameplay.move_log.clone(), gameplay.state)
+        };
+        let redo_log: String = {
+            let mut query = world.query::<&History>();
+            let history = query.iter().next()?.1;
+            history.redo.iter().rev().map(|record| record.step).collect()
+        };
+        if state == GameplayState::Won || (move_log.is_empty() && redo_log.is_empty()) {
+            return None;
+        }
+
+        let player = {
+            let mut query = world.query::<(&Position, &Player)>();
+            *query.iter().next()?.1.0
+        };
+        let boxes = world
+            .query::<(&Position, &Box)>()
+            .iter()
+            .map(|(_, (position, the_box))| SavedBox {
+                color: the_box.color,
+                position: *position,
+            })
+            .collect();
+
+        Some(SavedGame {
+            level,
+            ruleset,
+            move_count,
+            move_log,
+            redo_log,
+            player,
+            boxes,
+        })
+    }
+
+    /// 读取存档，没有存档或存档损坏时返回 None
+    pub fn load(ctx: &ggez::Context) -> Option<SavedGame> {
+        let path = SavedGame::path(ctx);
+        let text = fs::read_to_string(&path).ok()?;
+        toml::from_str(&text)
+            .map_err(|error| println!("ignoring corrupt savegame {}: {}", path.display(), error))
+            .ok()
+    }
+
+    /// 写入存档
+    pub fn save(&self, ctx: &ggez::Context) -> GameResult {
+        let path = SavedGame::path(ctx);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(self).map_err(|error| GameError::CustomError(error.to_string()))?;
+        fs::write(&path, text)?;
+        Ok(())
+    }
+
+    /// 删除存档（没有存档时什么也不做）
+    pub fn delete(ctx: &ggez::Context) {
+        let _ = fs::remove_file(SavedGame::path(ctx));
+    }
+
+    /// 把存档恢复到刚加载好的关卡上：重放移动记录和重做记录，再撤销重做部分，得到与保存时相同的历史
+    /// 重放失败或最终局面与存档不符（例如关卡文件被修改）时返回 false，调用方丢弃存档并重新开始本关
+    /// 恢复过程中产生的事件直接丢弃，不播放音效
+    pub fn restore(&self, world: &World) -> bool {
+        for c in self.move_log.chars().chain(self.redo_log.chars()) {
+            let Some(step) = SolverMove::from_lurd(c) else {
+                return false;
+            };
+            if !player_moved(&apply_action(world, step.direction)) {
+                return false;
+            }
+        }
+        for _ in self.redo_log.chars() {
+            undo(world);
+        }
+        self.matches(world)
+    }
+
+    /// 判断世界中的步数以及玩家和箱子的位置是否与存档一致
+    fn matches(&self, world: &World) -> bool {
+        let move_count = {
+            let mut query = world.query::<&GamePlay>();
+            query.iter().next().map(|(_, gameplay)| gameplay.move_count)
+        };
+        let player = {
+            let mut query = world.query::<(&Position, &Player)>();
+            query.iter().next().map(|(_, (position, _))| *position)
+        };
+        let mut boxes: Vec<(u8, u8, BoxColor)> = world
+            .query::<(&Position, &Box)>()
+            .iter()
+            .map(|(_, (position, the_box))| (position.x, position.y, the_box.color))
+            .collect();
+        let mut saved: Vec<(u8, u8, BoxColor)> = self
+            .boxes
+            .iter()
+            .map(|saved| (saved.position.x, saved.position.y, saved.color))
+            .collect();
+        boxes.sort();
+        saved.sort();
+        move_count == Some(self.move_count) && player == Some(self.player) && boxes == saved
+    }
+}
@@ -0,0 +1,55 @@
+use std::{fs, path::PathBuf};
+
+use ggez::{GameError, GameResult};
+use serde::{Deserialize, Serialize};
+
+use crate::theme::DEFAULT_THEME;
+
+/// 设置文件名（位于用户数据目录下）
+pub const SETTINGS_FILE: &str = "settings.toml";
+
+/// 玩家在设置菜单中的选择，下次启动时恢复
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// 视觉主题（主题目录名）
+    pub theme: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            theme: DEFAULT_THEME.to_string(),
+        }
+    }
+}
+
+impl Settings {
+    /// 设置文件路径
+    pub fn path(ctx: &ggez::Context) -> PathBuf {
+        ctx.fs.user_data_dir().join(SETTINGS_FILE)
+    }
+
+    /// 读取设置，没有设置文件或文件损坏时使用默认设置
+    pub fn load(ctx: &ggez::Context) -> Settings {
+        let path = Settings::path(ctx);
+        let Ok(text) = fs::read_to_string(&path) else {
+            return Settings::default();
+        };
+        toml::from_str(&text).unwrap_or_else(|error| {
+            println!("ignoring corrupt settings file {}: {}", path.display(), error);
+            Settings::default()
+        })
+    }
+
+    /// 写入设置
+    pub fn save(&self, ctx: &ggez::Context) -> GameResult {
+        let path = Settings::path(ctx);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(self).map_err(|error| GameError::CustomError(error.to_string()))?;
+        fs::write(&path, text)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+
+use hecs::{Entity, World};
+
+use crate::{
+    component::{Box, BoxColor, BoxSpot, GamePlay, History, Immovable, Moveable, MoveRecord, Player, Position, Ruleset},
+    grid::{Direction, Grid},
+    solver::SolverMove,
+    systems::{events::{BoxPlacedOnSpot, EntityMoved, Event}, gameplay::run_gameplay_state, hint::clear_hint},
+};
+
+/// 执行一个动作：让玩家朝指定方向走一步，返回这一步产生的事件
+/// 成功移动时记录步数、撤销历史和 LURD 移动记录，并更新胜负状态；
+/// 被挡住时局面不变，只产生 `PlayerHitObstacle` 事件
+/// 键盘输入、解法回放和存档恢复都通过这里移动，保证行为完全一致
+/// 这里不依赖 ggez，由调用方决定如何处理返回的事件（例如播放音效），可以在没有窗口时运行
+pub fn apply_action(world: &World, direction: Direction) -> Vec<Event> {
+    let mut to_move: Vec<(Entity, Direction)> = Vec::new();
+    let mut events = Vec::new();
+
+    // 获取网格，用于带边界检查的相邻格子查询
+    let grid = Grid::from_world(world);
+    // 获取本关规则，决定能否一次推动多个箱子
+    let ruleset = {
+        let mut query = world.query::<&Ruleset>();
+        query.iter().next().map(|(_, ruleset)| *ruleset).unwrap_or_default()
+    };
+
+    // 收集所有可移动实体（箱子）和不可移动实体（墙）的位置信息
+    let mov: HashMap<(u8, u8), Entity> = world
+        .query::<(&Position, &Moveable)>()
+        .iter()
+        .map(|t| ((t.1.0.x, t.1.0.y), t.0))
+        .collect::<HashMap<_, _>>();
+    let immov: HashMap<(u8, u8), Entity> = world
+        .query::<(&Position, &Immovable)>()
+        .iter()
+        .map(|t| ((t.1.0.x, t.1.0.y), t.0))
+        .collect::<HashMap<_, _>>();
+
+    // 遍历所有玩家实体（通常只有一个）
+    for (_, (position, _player)) in world.query::<(&Position, &Player)>().iter() {
+        // 从玩家所在位置开始，沿移动方向检测碰撞
+        let mut current = Some(*position);
+        loop {
+            // 走出地图边界与撞墙相同
+            let Some(pos) = current else {
+                to_move.clear();
+                events.push(Event::PlayerHitObstacle {});
+                break;
+            };
+
+            // 检查是否有可移动实体（箱子）
+            match mov.get(&(pos.x, pos.y)) {
+                // 经典规则下不能推动第二个箱子（to_move 中已有玩家和一个箱子）
+                Some(_) if ruleset == Ruleset::Classic && to_move.len() >= 2 => {
+                    to_move.clear();
+                    events.push(Event::PlayerHitObstacle {});
+                    break;
+                }
+                Some(entity) => to_move.push((*entity, direction)),
+                None => {
+                    // 没有可移动的实体，检查是否有不可移动的实体（墙）
+                    match immov.get(&(pos.x, pos.y)) {
+                        Some(_id) => {
+                            // 撞墙了，清空移动列表（推不动），触发撞墙音效事件
+                            to_move.clear();
+                            events.push(Event::PlayerHitObstacle {});
+                            break;
+                        },
+                        None => break, // 空地，可以移动
+                    }
+                }
+            }
+            current = grid.neighbour(&pos, direction);
+        }
+    }
+
+    // 除玩家外还有实体移动，说明这一步推动了箱子
+    let step = SolverMove {
+        direction,
+        push: to_move.len() > 1,
+    }
+    .to_lurd();
+
+    // 执行实际的移动操作，同时记录移动前后的位置用于撤销
+    let mut record = MoveRecord { moves: Vec::new(), step };
+    for (entity, direction) in to_move {
+        let mut position = world.get::<&mut Position>(entity).unwrap();
+        let from = *position;
+
+        // 碰撞检测已经保证目标格子在地图内
+        if let Some(next) = grid.neighbour(&position, direction) {
+            *position = next;
+        }
+        record.moves.push((entity, from, *position));
+
+        // 触发实体移动事件（用于播放音效等）
+        events.push(Event::EntityMoved(EntityMoved { entity }));
+    }
+
+    let moved = !record.moves.is_empty();
+    if moved {
+        // 箱子被推到目标点上时产生归位事件
+        let placed = boxes_placed_on_spots(world, &events);
+        events.extend(placed);
+
+        // 增加步数等统计并追加移动记录
+        {
+            let mut query = world.query::<&mut GamePlay>();
+            let gameplay = query.iter().next().unwrap().1;
+            gameplay.record_step(step);
+        }
+
+        // 新的一步会使重做栈失效
+        {
+            let mut query = world.query::<&mut History>();
+            let history = query.iter().next().unwrap().1;
+            history.undo.push(record);
+            history.redo.clear();
+        }
+
+        // 局面已经变化，之前的提示不再有效
+        clear_hint(world);
+
+        // 检查胜负
+        run_gameplay_state(world);
+    }
+
+    events
+}
+
+/// 判断玩家朝指定方向走一步是否会碰到箱子（即这一步是推动，不论能否推得动）
+pub fn would_push(world: &World, direction: Direction) -> bool {
+    let grid = Grid::from_world(world);
+    let target = {
+        let mut query = world.query::<(&Position, &Player)>();
+        query.iter().next().and_then(|(_, (position, _))| grid.neighbour(position, direction))
+    };
+    let Some(target) = target else {
+        return false;
+    };
+    world
+        .query::<(&Position, &Box)>()
+        .iter()
+        .any(|(_, (position, _))| position.x == target.x && position.y == target.y)
+}
+
+/// 判断动作是否让玩家移动了（没有被墙或箱子挡住）
+pub fn player_moved(events: &[Event]) -> bool {
+    events.iter().any(|event| matches!(event, Event::EntityMoved(_)))
+}
+
+/// 根据实体移动事件找出停在目标点上的箱子，每个产生一个归位事件
+pub(crate) fn boxes_placed_on_spots(world: &World, events: &[Event]) -> Vec<Event> {
+    let box_spots_by_position: HashMap<(u8, u8), BoxColor> = world
+        .query::<(&Position, &BoxSpot)>()
+        .iter()
+        .map(|(_, (position, box_spot))| ((position.x, position.y), box_spot.color))
+        .collect();
+
+    events
+        .iter()
+        .filter_map(|event| {
+            let Event::EntityMoved(EntityMoved { entity }) = event else {
+                return None;
+            };
+            let the_box = world.get::<&Box>(*entity).ok()?;
+            let position = world.get::<&Position>(*entity).ok()?;
+            let spot_color = box_spots_by_position.get(&(position.x, position.y))?;
+            Some(Event::BoxPlacedOnSpot(BoxPlacedOnSpot {
+                is_correct_spot: *spot_color == the_box.color,
+            }))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use hecs::{Entity, World};
+
+    use super::apply_action;
+    use crate::{
+        component::{Box, GamePlay, GameplayState, History, Player, Position, Ruleset},
+        entity,
+        grid::Direction,
+        map::load_level_map,
+        systems::{events::{BoxPlacedOnSpot, EntityMoved, Event}, history},
+    };
+
+    /// 一排走廊：玩家、一个箱子、一个目标点
+    const CORRIDOR: &str = "
+        W W W W W W W
+        W P . BB . BS W
+        W W W W W W W
+    ";
+
+    /// 玩家右边紧挨着两个箱子，第三列之后还有两格空地
+    const TRAIN: &str = "
+        W W W W W W W
+        W P BB BB . . W
+        W . . . BS BS W
+        W W W W W W W
+    ";
+
+    /// 从地图字符串创建一个可以直接执行动作的世界
+    fn world_from(map: &str, ruleset: Ruleset) -> World {
+        let mut world = World::new();
+        assert!(load_level_map(&mut world, map.to_string()).is_ok(), "invalid test map");
+        entity::create_ruleset(&mut world, ruleset);
+        entity::create_gameplay(&mut world);
+        entity::create_history(&mut world);
+        world
+    }
+
+    fn player(world: &World) -> (Entity, (u8, u8)) {
+        let mut query = world.query::<(&Position, &Player)>();
+        let (entity, (position, _)) = query.iter().next().unwrap();
+        (entity, (position.x, position.y))
+    }
+
+    /// 所有箱子的位置，按坐标排序
+    fn boxes(world: &World) -> Vec<(u8, u8)> {
+        let mut positions: Vec<(u8, u8)> = world
+            .query::<(&Position, &Box)>()
+            .iter()
+            .map(|(_, (position, _))| (position.x, position.y))
+            .collect();
+        positions.sort();
+        positions
+    }
+
+    fn gameplay(world: &World) -> (String, u32, u32, GameplayState) {
+        let mut query = world.query::<&GamePlay>();
+        let gameplay = query.iter().next().unwrap().1;
+        (gameplay.move_log.clone(), gameplay.move_count, gameplay.push_count, gameplay.state)
+    }
+
+    #[test]
+    fn walking_and_pushing() {
+        let world = world_from(CORRIDOR, Ruleset::Classic);
+        let (player_entity, _) = player(&world);
+
+        // 走一步：只有玩家移动，记录小写字母
+        let events = apply_action(&world, Direction::Right);
+        assert_eq!(events, vec![Event::EntityMoved(EntityMoved { entity: player_entity })]);
+        assert_eq!(player(&world).1, (2, 1));
+        assert_eq!(boxes(&world), vec![(3, 1)]);
+        assert_eq!(gameplay(&world).0, "r");
+
+        // 推一步：玩家和箱子都移动，记录大写字母
+        let events = apply_action(&world, Direction::Right);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0], Event::EntityMoved(EntityMoved { entity: player_entity }));
+        assert_eq!(player(&world).1, (3, 1));
+        assert_eq!(boxes(&world), vec![(4, 1)]);
+        assert_eq!(gameplay(&world).0, "rR");
+
+        // 箱子推到目标点上：产生归位事件并获胜
+        let events = apply_action(&world, Direction::Right);
+        assert_eq!(events.last(), Some(&Event::BoxPlacedOnSpot(BoxPlacedOnSpot { is_correct_spot: true })));
+        assert_eq!(boxes(&world), vec![(5, 1)]);
+        assert_eq!(gameplay(&world), ("rRR".to_string(), 3, 2, GameplayState::Won));
+    }
+
+    #[test]
+    fn wall_blocks_the_player() {
+        let world = world_from(CORRIDOR, Ruleset::Classic);
+
+        let events = apply_action(&world, Direction::Left);
+        assert_eq!(events, vec![Event::PlayerHitObstacle]);
+        assert_eq!(player(&world).1, (1, 1));
+        assert_eq!(gameplay(&world), (String::new(), 0, 0, GameplayState::Playing));
+        assert!(world.query::<&History>().iter().next().unwrap().1.undo.is_empty());
+    }
+
+    #[test]
+    fn classic_rules_block_two_boxes() {
+        let world = world_from(TRAIN, Ruleset::Classic);
+
+        let events = apply_action(&world, Direction::Right);
+        assert_eq!(events, vec![Event::PlayerHitObstacle]);
+        assert_eq!(player(&world).1, (1, 1));
+        assert_eq!(boxes(&world), vec![(2, 1), (3, 1)]);
+        assert_eq!(gameplay(&world).0, "");
+    }
+
+    #[test]
+    fn multi_push_moves_the_train_until_a_wall() {
+        let world = world_from(TRAIN, Ruleset::MultiPush);
+
+        // 一次推动一整排箱子，只算一次推动
+        let events = apply_action(&world, Direction::Right);
+        assert_eq!(events.iter().filter(|event| matches!(event, Event::EntityMoved(_))).count(), 3);
+        assert_eq!(player(&world).1, (2, 1));
+        assert_eq!(boxes(&world), vec![(3, 1), (4, 1)]);
+
+        apply_action(&world, Direction::Right);
+        assert_eq!(boxes(&world), vec![(4, 1), (5, 1)]);
+        assert_eq!(gameplay(&world), ("RR".to_string(), 2, 2, GameplayState::Playing));
+
+        // 最前面的箱子顶到墙，整排都推不动
+        let events = apply_action(&world, Direction::Right);
+        assert_eq!(events, vec![Event::PlayerHitObstacle]);
+        assert_eq!(player(&world).1, (3, 1));
+        assert_eq!(boxes(&world), vec![(4, 1), (5, 1)]);
+        assert_eq!(gameplay(&world).0, "RR");
+    }
+
+    #[test]
+    fn undo_and_redo_round_trip() {
+        let world = world_from(CORRIDOR, Ruleset::Classic);
+        apply_action(&world, Direction::Right);
+        apply_action(&world, Direction::Right);
+
+        assert!(history::undo(&world));
+        assert!(history::undo(&world));
+        assert!(!history::undo(&world));
+        assert_eq!(player(&world).1, (1, 1));
+        assert_eq!(boxes(&world), vec![(3, 1)]);
+        assert_eq!(gameplay(&world), (String::new(), 0, 0, GameplayState::Playing));
+
+        // 重做产生与原来的移动相同的事件
+        let (player_entity, _) = player(&world);
+        assert_eq!(history::redo(&world), vec![Event::EntityMoved(EntityMoved { entity: player_entity })]);
+        assert_eq!(history::redo(&world).len(), 2);
+        assert_eq!(player(&world).1, (3, 1));
+        assert_eq!(boxes(&world), vec![(4, 1)]);
+        assert_eq!(gameplay(&world), ("rR".to_string(), 2, 1, GameplayState::Playing));
+
+        // 撤销后走新的一步会清空重做栈
+        history::undo(&world);
+        apply_action(&world, Direction::Left);
+        assert!(history::redo(&world).is_empty());
+        assert_eq!(gameplay(&world).0, "rl");
+    }
+}
@@ -0,0 +1,156 @@
+use std::{fmt::Display, fs, path::PathBuf};
+
+use ggez::GameResult;
+use hecs::World;
+
+use crate::{level_pack::LevelLibrary, simulation::{apply_action, player_moved}, solver::SolverMove};
+
+/// 解法文件所在的目录（位于用户数据目录下）
+pub const SOLUTION_DIR: &str = "solutions";
+
+/// 导入解法失败的原因
+#[derive(Debug, PartialEq, Eq)]
+pub enum SolutionError {
+    /// 读取解法文件失败
+    Io(String),
+    /// 解法中没有任何移动
+    Empty,
+    /// 第 step 步不是 LURD 字符
+    InvalidChar { step: usize, ch: char },
+    /// 第 step 步无法执行（被墙或箱子挡住）
+    Blocked { step: usize },
+}
+
+impl Display for SolutionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolutionError::Io(error) => write!(f, "{}", error),
+            SolutionError::Empty => write!(f, "empty solution"),
+            SolutionError::InvalidChar { step, ch } => write!(f, "invalid move '{}' at step {}", ch, step),
+            SolutionError::Blocked { step } => write!(f, "step {} is blocked", step),
+        }
+    }
+}
+
+/// 解析 LURD 字符串：l/u/r/d 表示行走，大写表示推动
+/// 忽略空白和 `;` 开头的注释行
+pub fn parse_lurd(text: &str) -> Result<Vec<SolverMove>, SolutionError> {
+    let moves = text
+        .lines()
+        .filter(|line| !line.trim_start().starts_with(';'))
+        .flat_map(|line| line.chars().filter(|c| !c.is_whitespace()))
+        .enumerate()
+        .map(|(index, ch)| SolverMove::from_lurd(ch).ok_or(SolutionError::InvalidChar { step: index + 1, ch }))
+        .collect::<Result<Vec<_>, _>>()?;
+    if moves.is_empty() {
+        return Err(SolutionError::Empty);
+    }
+    Ok(moves)
+}
+
+/// 在当前局面上依次执行解法中的每一步
+/// 与键盘输入走同一条移动逻辑，推动标记以实际局面为准
+pub fn apply_solution(world: &World, moves: &[SolverMove]) -> Result<(), SolutionError> {
+    for (index, step) in moves.iter().enumerate() {
+        if !player_moved(&apply_action(world, step.direction)) {
+            return Err(SolutionError::Blocked { step: index + 1 });
+        }
+    }
+    Ok(())
+}
+
+/// 关卡解法文件的路径：用户数据目录/solutions/<关卡标识>.txt
+pub fn solution_path(ctx: &ggez::Context, library: &LevelLibrary, level_index: usize) -> PathBuf {
+    ctx.fs
+        .user_data_dir()
+        .join(SOLUTION_DIR)
+        .join(format!("{}.txt", library.level_key(level_index)))
+}
+
+/// 把 LURD 移动记录导出为解法文件，返回文件路径
+/// 文件开头的注释行记录关卡名称、步数和推动次数
+pub fn export_solution(ctx: &ggez::Context, library: &LevelLibrary, level_index: usize, move_log: &str) -> GameResult<PathBuf> {
+    let path = solution_path(ctx, library, level_index);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let pushes = move_log.chars().filter(|c| c.is_ascii_uppercase()).count();
+    let text = format!(
+        "; {}\n; {} moves, {} pushes\n{}\n",
+        library.display_name(level_index),
+        move_log.len(),
+        pushes,
+        move_log
+    );
+    fs::write(&path, text)?;
+    Ok(path)
+}
+
+/// 读取并解析关卡的解法文件
+pub fn import_solution(ctx: &ggez::Context, library: &LevelLibrary, level_index: usize) -> Result<Vec<SolverMove>, SolutionError> {
+    let path = solution_path(ctx, library, level_index);
+    let text = fs::read_to_string(&path).map_err(|error| SolutionError::Io(error.to_string()))?;
+    parse_lurd(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use hecs::World;
+
+    use super::{apply_solution, parse_lurd, SolutionError};
+    use crate::{
+        component::{GamePlay, GameplayState, Ruleset},
+        entity,
+        grid::Direction,
+        map::load_level_map,
+        solver::SolverMove,
+    };
+
+    fn corridor() -> World {
+        let mut world = World::new();
+        assert!(load_level_map(&mut world, "#######\n#@$  .#\n#######".to_string()).is_ok(), "invalid test map");
+        entity::create_ruleset(&mut world, Ruleset::Classic);
+        entity::create_gameplay(&mut world);
+        entity::create_history(&mut world);
+        world
+    }
+
+    #[test]
+    fn parses_moves_and_pushes_ignoring_comments_and_whitespace() {
+        let moves = parse_lurd("; 导出的解法\nlU\n r D\n").unwrap();
+        assert_eq!(
+            moves,
+            vec![
+                SolverMove { direction: Direction::Left, push: false },
+                SolverMove { direction: Direction::Up, push: true },
+                SolverMove { direction: Direction::Right, push: false },
+                SolverMove { direction: Direction::Down, push: true },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_the_step_of_a_bad_character() {
+        // 空白不计入步数
+        assert_eq!(parse_lurd("rR\nrx"), Err(SolutionError::InvalidChar { step: 4, ch: 'x' }));
+        assert_eq!(parse_lurd("R1"), Err(SolutionError::InvalidChar { step: 2, ch: '1' }));
+    }
+
+    #[test]
+    fn empty_solution() {
+        assert_eq!(parse_lurd(""), Err(SolutionError::Empty));
+        assert_eq!(parse_lurd("; only a comment\n\n"), Err(SolutionError::Empty));
+    }
+
+    #[test]
+    fn applies_a_solution_or_reports_the_blocked_step() {
+        let world = corridor();
+        assert_eq!(apply_solution(&world, &parse_lurd("RRR").unwrap()), Ok(()));
+        let mut query = world.query::<&GamePlay>();
+        assert_eq!(query.iter().next().unwrap().1.state, GameplayState::Won);
+
+        let world = corridor();
+        assert_eq!(apply_solution(&world, &parse_lurd("Rul").unwrap()), Err(SolutionError::Blocked { step: 2 }));
+    }
+}
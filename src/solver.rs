@@ -0,0 +1,743 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, VecDeque},
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use hecs::World;
+
+use crate::{component::{Box, BoxColor, BoxSpot, Player, Position, Ruleset, Wall}, deadlock::DeadlockAnalysis, grid::{Direction, Grid}};
+
+/// 不可达格子的推动距离
+pub const UNREACHABLE: u32 = u32::MAX;
+
+/// 求解器的搜索限制
+#[derive(Clone, Copy)]
+pub struct SolverLimits {
+    /// 最多展开的节点数
+    pub max_nodes: usize,
+    /// 最长搜索时间
+    pub max_time: Duration,
+}
+
+impl Default for SolverLimits {
+    fn default() -> Self {
+        Self {
+            max_nodes: 200_000,
+            max_time: Duration::from_secs(3),
+        }
+    }
+}
+
+/// 解中的一步：移动方向以及这一步是否推动了箱子
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SolverMove {
+    pub direction: Direction,
+    pub push: bool,
+}
+
+impl SolverMove {
+    /// 转换为 LURD 记法：l/u/r/d 表示行走，大写表示推动
+    pub fn to_lurd(self) -> char {
+        let c = match self.direction {
+            Direction::Left => 'l',
+            Direction::Up => 'u',
+            Direction::Right => 'r',
+            Direction::Down => 'd',
+        };
+        if self.push { c.to_ascii_uppercase() } else { c }
+    }
+
+    /// 解析一个 LURD 字符，不是 LURD 字符时返回 None
+    pub fn from_lurd(c: char) -> Option<SolverMove> {
+        let direction = match c.to_ascii_lowercase() {
+            'l' => Direction::Left,
+            'u' => Direction::Up,
+            'r' => Direction::Right,
+            'd' => Direction::Down,
+            _ => return None,
+        };
+        Some(SolverMove {
+            direction,
+            push: c.is_ascii_uppercase(),
+        })
+    }
+}
+
+/// 求解结果
+pub struct Solution {
+    /// 完整的移动序列（包括走路和推箱子）
+    pub moves: Vec<SolverMove>,
+    /// 推动次数（搜索保证该值最优）
+    pub pushes: usize,
+    /// 搜索过程中展开的节点数
+    pub nodes: usize,
+}
+
+impl Solution {
+    /// 以 LURD 字符串表示的解
+    pub fn lurd(&self) -> String {
+        self.moves.iter().map(|step| step.to_lurd()).collect()
+    }
+}
+
+/// 求解失败的原因
+#[derive(Debug, PartialEq, Eq)]
+pub enum SolveError {
+    /// 关卡中没有玩家
+    NoPlayer,
+    /// 搜索完所有局面仍然无解
+    Unsolvable,
+    /// 达到节点数上限
+    NodeLimit,
+    /// 达到时间上限
+    TimeLimit,
+}
+
+impl Display for SolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SolveError::NoPlayer => "no player",
+            SolveError::Unsolvable => "unsolvable",
+            SolveError::NodeLimit => "node limit reached",
+            SolveError::TimeLimit => "time limit reached",
+        })?;
+        Ok(())
+    }
+}
+
+/// 求解器使用的棋盘：静态的墙和目标点，以及箱子和玩家的初始位置
+/// 格子编号与 `Grid` 一致，地图外视为墙
+#[derive(Clone)]
+pub struct Board {
+    pub grid: Grid,
+    /// 推箱子规则：经典规则一次只能推一个箱子，多箱推动可以推动一整排
+    pub ruleset: Ruleset,
+    walls: Vec<bool>,
+    goals: Vec<Option<BoxColor>>,
+    boxes: Vec<(usize, BoxColor)>,
+    player: usize,
+}
+
+impl Board {
+    /// 从 ECS 世界中读取当前局面
+    pub fn from_world(world: &World) -> Result<Board, SolveError> {
+        let grid = Grid::from_world(world);
+
+        let mut walls = vec![false; grid.cell_count()];
+        for (_, (position, _)) in world.query::<(&Position, &Wall)>().iter() {
+            if let Some(cell) = grid.index(position) {
+                walls[cell] = true;
+            }
+        }
+
+        let mut goals = vec![None; grid.cell_count()];
+        for (_, (position, box_spot)) in world.query::<(&Position, &BoxSpot)>().iter() {
+            if let Some(cell) = grid.index(position) {
+                goals[cell] = Some(box_spot.color);
+            }
+        }
+
+        let boxes = world
+            .query::<(&Position, &Box)>()
+            .iter()
+            .filter_map(|(_, (position, the_box))| Some((grid.index(position)?, the_box.color)))
+            .collect();
+
+        let player = world
+            .query::<(&Position, &Player)>()
+            .iter()
+            .find_map(|(_, (position, _))| grid.index(position))
+            .ok_or(SolveError::NoPlayer)?;
+
+        let ruleset = {
+            let mut query = world.query::<&Ruleset>();
+            query.iter().next().map(|(_, ruleset)| *ruleset).unwrap_or_default()
+        };
+
+        Ok(Board {
+            grid,
+            ruleset,
+            walls,
+            goals,
+            boxes,
+            player,
+        })
+    }
+
+    /// 格子数量
+    pub fn cell_count(&self) -> usize {
+        self.walls.len()
+    }
+
+    /// 格子是否是墙
+    pub fn is_wall(&self, cell: usize) -> bool {
+        self.walls[cell]
+    }
+
+    /// 格子上目标点的颜色
+    pub fn goal(&self, cell: usize) -> Option<BoxColor> {
+        self.goals[cell]
+    }
+
+    /// 所有箱子所在的格子和颜色
+    pub fn boxes(&self) -> &[(usize, BoxColor)] {
+        &self.boxes
+    }
+
+    /// 从格子沿指定方向移动一格，越界返回 None
+    pub fn step(&self, cell: usize, direction: Direction) -> Option<usize> {
+        self.grid.step(cell, direction)
+    }
+
+    /// 沿指定方向移动一格后的格子，越界或是墙则返回 None
+    fn open_step(&self, cell: usize, direction: Direction) -> Option<usize> {
+        self.step(cell, direction).filter(|&next| !self.walls[next])
+    }
+
+    /// 计算每种颜色的箱子从各个格子推到同色目标点所需的最少推动次数
+    /// 忽略其他箱子，结果可作为启发函数，不可达的格子即为该颜色的死格
+    pub fn push_distances(&self) -> HashMap<BoxColor, Vec<u32>> {
+        let mut distances = HashMap::new();
+        for color in self.goals.iter().flatten() {
+            if distances.contains_key(color) {
+                continue;
+            }
+
+            let mut distance = vec![UNREACHABLE; self.walls.len()];
+            let mut queue = VecDeque::new();
+            for (cell, goal) in self.goals.iter().enumerate() {
+                if *goal == Some(*color) {
+                    distance[cell] = 0;
+                    queue.push_back(cell);
+                }
+            }
+
+            // 反向搜索：箱子从 from 沿 direction 推到 cell，玩家需要站在 from 的另一侧
+            while let Some(cell) = queue.pop_front() {
+                for direction in Direction::ALL {
+                    let Some(from) = self.open_step(cell, direction.opposite()) else {
+                        continue;
+                    };
+                    if self.open_step(from, direction.opposite()).is_none() {
+                        continue;
+                    }
+                    if distance[from] == UNREACHABLE {
+                        distance[from] = distance[cell] + 1;
+                        queue.push_back(from);
+                    }
+                }
+            }
+            distances.insert(*color, distance);
+        }
+        distances
+    }
+
+    /// 从 cell 沿 direction 推动时会被一起推动的箱子（从 cell 开始依次排列）
+    /// 经典规则下只能推动一个箱子；最后一个箱子前方必须是空地，否则返回 None
+    fn train(&self, cell: usize, direction: Direction, occupied: &[bool]) -> Option<Vec<usize>> {
+        let mut train = vec![cell];
+        loop {
+            let next = self.open_step(*train.last().unwrap(), direction)?;
+            if !occupied[next] {
+                return Some(train);
+            }
+            if self.ruleset == Ruleset::Classic {
+                return None;
+            }
+            train.push(next);
+        }
+    }
+
+    /// 计算玩家在不推箱子的情况下能到达的所有格子
+    fn reachable(&self, player: usize, occupied: &[bool]) -> Vec<bool> {
+        let mut visited = vec![false; self.walls.len()];
+        let mut stack = vec![player];
+        visited[player] = true;
+        while let Some(cell) = stack.pop() {
+            for direction in Direction::ALL {
+                if let Some(next) = self.open_step(cell, direction).filter(|&next| !visited[next] && !occupied[next]) {
+                    visited[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+        visited
+    }
+
+    /// 玩家在当前局面下不推箱子走到 to 的最短路径，走不到（墙、箱子或被挡住）时返回 None
+    pub fn player_path(&self, to: usize) -> Option<Vec<Direction>> {
+        let mut occupied = vec![false; self.walls.len()];
+        for (cell, _) in self.boxes.iter() {
+            occupied[*cell] = true;
+        }
+        if self.walls[to] || occupied[to] {
+            return None;
+        }
+        self.walk_path(self.player, to, &occupied)
+    }
+
+    /// 只推动 from 处的箱子把它推到 to 的最短移动序列（包括走路和推动），做不到时返回 None
+    /// 其他箱子视为障碍，不能被一起推动，因此多箱推动规则下的结果与经典规则相同
+    pub fn box_path(&self, from: usize, to: usize) -> Option<Vec<Direction>> {
+        let mut occupied = vec![false; self.walls.len()];
+        for (cell, _) in self.boxes.iter().filter(|(cell, _)| *cell != from) {
+            occupied[*cell] = true;
+        }
+        if !self.boxes.iter().any(|(cell, _)| *cell == from) || self.walls[to] || occupied[to] {
+            return None;
+        }
+
+        // 以 (玩家, 箱子) 为状态做广度优先搜索，每一步是一次走路或推动
+        let start = (self.player, from);
+        let mut previous: HashMap<(usize, usize), ((usize, usize), Direction)> = HashMap::new();
+        let mut queue = VecDeque::from([start]);
+        while let Some((player, the_box)) = queue.pop_front() {
+            if the_box == to {
+                let mut path = Vec::new();
+                let mut current = (player, the_box);
+                while let Some(&(prev, direction)) = previous.get(&current) {
+                    path.push(direction);
+                    current = prev;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for direction in Direction::ALL {
+                let Some(next) = self.open_step(player, direction).filter(|&next| !occupied[next]) else {
+                    continue;
+                };
+                let state = if next == the_box {
+                    match self.open_step(the_box, direction).filter(|&pushed| !occupied[pushed]) {
+                        Some(pushed) => (next, pushed),
+                        None => continue,
+                    }
+                } else {
+                    (next, the_box)
+                };
+                if state != start && !previous.contains_key(&state) {
+                    previous.insert(state, ((player, the_box), direction));
+                    queue.push_back(state);
+                }
+            }
+        }
+        None
+    }
+
+    /// 玩家在不推箱子的情况下从 from 走到 to 的最短路径
+    fn walk_path(&self, from: usize, to: usize, occupied: &[bool]) -> Option<Vec<Direction>> {
+        let mut previous: Vec<Option<(usize, Direction)>> = vec![None; self.walls.len()];
+        let mut visited = vec![false; self.walls.len()];
+        let mut queue = VecDeque::from([from]);
+        visited[from] = true;
+
+        while let Some(cell) = queue.pop_front() {
+            if cell == to {
+                let mut path = Vec::new();
+                let mut current = to;
+                while let Some((prev, direction)) = previous[current] {
+                    path.push(direction);
+                    current = prev;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for direction in Direction::ALL {
+                if let Some(next) = self.open_step(cell, direction).filter(|&next| !visited[next] && !occupied[next]) {
+                    visited[next] = true;
+                    previous[next] = Some((cell, direction));
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// 搜索中的局面：玩家可达区域中编号最小的格子，以及按格子排序的箱子
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct State {
+    player: usize,
+    boxes: Vec<(usize, BoxColor)>,
+}
+
+/// 搜索树节点
+struct Node {
+    state: State,
+    parent: usize,
+    /// 从父节点到该节点的推动：(箱子原来的格子, 推动方向)
+    push: Option<(usize, Direction)>,
+    pushes: u32,
+}
+
+/// 使用 A* 搜索推动次数最少的解
+/// 搜索以推动为单位展开，玩家在两次推动之间的走路由可达区域表示；
+/// 每个箱子只能推到同色目标点上，能否一次推动一排箱子由棋盘的规则决定
+/// 只保证推动次数最优：推动次数相同的解之间不比较步数，走路部分是每次推动前的最短路径
+pub fn solve(board: &Board, limits: &SolverLimits) -> Result<Solution, SolveError> {
+    let started = Instant::now();
+    let distances = board.push_distances();
+    let deadlock = DeadlockAnalysis::new(board);
+
+    // 统计每种颜色的箱子和目标点数量
+    let mut box_counts: HashMap<BoxColor, usize> = HashMap::new();
+    let mut goal_counts: HashMap<BoxColor, usize> = HashMap::new();
+    for (_, color) in board.boxes.iter() {
+        *box_counts.entry(*color).or_default() += 1;
+    }
+    for color in board.goals.iter().flatten() {
+        *goal_counts.entry(*color).or_default() += 1;
+    }
+    if goal_counts
+        .iter()
+        .any(|(color, goals)| box_counts.get(color).copied().unwrap_or(0) < *goals)
+    {
+        return Err(SolveError::Unsolvable);
+    }
+    // 箱子与目标点数量相同的颜色，每个箱子都必须归位，可以用于剪枝和启发
+    let exact = |color: &BoxColor| box_counts.get(color) == goal_counts.get(color);
+
+    // 经典规则下每次推动只让一个箱子前进一格，启发值取距离之和；
+    // 多箱推动时一次推动可能让一排箱子都前进，只能取最大距离才能保证最优
+    let heuristic = |boxes: &[(usize, BoxColor)]| -> Option<u32> {
+        let mut total = 0;
+        let mut max = 0;
+        for (cell, color) in boxes.iter().filter(|(_, color)| exact(color)) {
+            match distances[color][*cell] {
+                UNREACHABLE => return None,
+                distance => {
+                    total += distance;
+                    max = max.max(distance);
+                }
+            }
+        }
+        Some(match board.ruleset {
+            Ruleset::Classic => total,
+            Ruleset::MultiPush => max,
+        })
+    };
+
+    let occupancy = |boxes: &[(usize, BoxColor)]| {
+        let mut occupied = vec![false; board.walls.len()];
+        for (cell, _) in boxes.iter() {
+            occupied[*cell] = true;
+        }
+        occupied
+    };
+    let normalize = |player: usize, occupied: &[bool]| {
+        let reach = board.reachable(player, occupied);
+        let first = reach.iter().position(|&r| r).unwrap_or(player);
+        (first, reach)
+    };
+
+    let mut initial_boxes = board.boxes.clone();
+    initial_boxes.sort();
+    let Some(initial_h) = heuristic(&initial_boxes) else {
+        return Err(SolveError::Unsolvable);
+    };
+    let (initial_player, _) = normalize(board.player, &occupancy(&initial_boxes));
+    let initial = State {
+        player: initial_player,
+        boxes: initial_boxes,
+    };
+
+    let mut nodes = vec![Node {
+        state: initial.clone(),
+        parent: 0,
+        push: None,
+        pushes: 0,
+    }];
+    let mut best: HashMap<State, u32> = HashMap::from([(initial, 0)]);
+    let mut open = BinaryHeap::from([(Reverse(initial_h), Reverse(initial_h), 0usize)]);
+    let mut expanded = 0;
+
+    while let Some((_, _, index)) = open.pop() {
+        let pushes = nodes[index].pushes;
+        if best.get(&nodes[index].state).is_some_and(|&g| g < pushes) {
+            continue; // 已经找到更短的路径
+        }
+
+        if is_solved(board, &nodes[index].state.boxes) {
+            return Ok(reconstruct(board, &nodes, index, expanded));
+        }
+
+        expanded += 1;
+        if expanded > limits.max_nodes {
+            return Err(SolveError::NodeLimit);
+        }
+        if expanded % 1024 == 0 && started.elapsed() > limits.max_time {
+            return Err(SolveError::TimeLimit);
+        }
+
+        let state = nodes[index].state.clone();
+        let occupied = occupancy(&state.boxes);
+        let reach = board.reachable(state.player, &occupied);
+
+        for &(cell, _) in state.boxes.iter() {
+            for direction in Direction::ALL {
+                // 玩家站在箱子后面
+                let Some(behind) = board.open_step(cell, direction.opposite()) else {
+                    continue;
+                };
+                if !reach[behind] {
+                    continue;
+                }
+                // 被推动的一排箱子，前方必须是空地
+                let Some(train) = board.train(cell, direction, &occupied) else {
+                    continue;
+                };
+
+                let mut boxes = state.boxes.clone();
+                for moved in train.iter() {
+                    let i = boxes.iter().position(|(c, _)| c == moved).unwrap();
+                    boxes[i].0 = board.step(*moved, direction).unwrap();
+                }
+                boxes.sort();
+                let Some(child_h) = heuristic(&boxes) else {
+                    continue; // 推进了死格
+                };
+                if deadlock.find_deadlock(&boxes).is_some() {
+                    continue; // 推动后出现冻结死锁
+                }
+
+                let (player, _) = normalize(cell, &occupancy(&boxes));
+                let child = State { player, boxes };
+                let child_pushes = pushes + 1;
+                if best.get(&child).is_some_and(|&g| g <= child_pushes) {
+                    continue;
+                }
+                best.insert(child.clone(), child_pushes);
+                nodes.push(Node {
+                    state: child,
+                    parent: index,
+                    push: Some((cell, direction)),
+                    pushes: child_pushes,
+                });
+                open.push((Reverse(child_pushes + child_h), Reverse(child_h), nodes.len() - 1));
+            }
+        }
+    }
+
+    Err(SolveError::Unsolvable)
+}
+
+/// 判断是否每个目标点上都有同色的箱子
+fn is_solved(board: &Board, boxes: &[(usize, BoxColor)]) -> bool {
+    board.goals.iter().enumerate().all(|(cell, goal)| match goal {
+        Some(color) => boxes.binary_search(&(cell, *color)).is_ok(),
+        None => true,
+    })
+}
+
+/// 从搜索树还原完整的移动序列，补全每次推动前玩家走路的路径
+fn reconstruct(board: &Board, nodes: &[Node], goal: usize, expanded: usize) -> Solution {
+    let mut pushes = Vec::new();
+    let mut index = goal;
+    while let Some(push) = nodes[index].push {
+        pushes.push(push);
+        index = nodes[index].parent;
+    }
+    pushes.reverse();
+
+    let mut occupied = vec![false; board.walls.len()];
+    for (cell, _) in board.boxes.iter() {
+        occupied[*cell] = true;
+    }
+    let mut player = board.player;
+    let mut moves = Vec::new();
+    for (cell, direction) in pushes.iter() {
+        let behind = board.step(*cell, direction.opposite()).unwrap();
+        let train = board.train(*cell, *direction, &occupied).unwrap_or_default();
+        let target = board.step(*train.last().unwrap_or(cell), *direction).unwrap();
+        let path = board.walk_path(player, behind, &occupied).unwrap_or_default();
+        moves.extend(path.into_iter().map(|direction| SolverMove { direction, push: false }));
+        moves.push(SolverMove {
+            direction: *direction,
+            push: true,
+        });
+        occupied[*cell] = false;
+        occupied[target] = true;
+        player = *cell;
+    }
+
+    Solution {
+        moves,
+        pushes: pushes.len(),
+        nodes: expanded,
+    }
+}
+
+/// 从 ECS 世界读取当前局面并求解
+pub fn solve_world(world: &World, limits: &SolverLimits) -> Result<Solution, SolveError> {
+    solve(&Board::from_world(world)?, limits)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use hecs::World;
+
+    use super::{solve, Board, SolveError, SolverLimits, SolverMove, UNREACHABLE};
+    use crate::{
+        component::{BoxColor, GamePlay, GameplayState, Position, Ruleset},
+        entity,
+        grid::Direction,
+        map::load_level_map,
+        simulation::apply_action,
+    };
+
+    /// 一个箱子，推三次到目标点
+    const CORRIDOR: &str = "
+        #######
+        #@$  .#
+        #######
+    ";
+
+    /// 两个箱子挨在一起，只有一次推动一排才能推到目标点
+    const TRAIN: &str = "
+        ########
+        #@$$ ..#
+        ########
+    ";
+
+    /// 红箱子要经过蓝目标点才能到达红目标点
+    const COLORED: &str = "
+        W W W W W W W W
+        W P . RB BS RS . W
+        W . . BB . . . W
+        W . . . . . . W
+        W W W W W W W W
+    ";
+
+    /// 箱子在角落里，永远推不出来
+    const CORNERED: &str = "
+        #####
+        #$ .#
+        # @ #
+        #####
+    ";
+
+    /// 箱子较多的大房间，搜索需要展开很多节点
+    const ROOM: &str = "
+        ##########
+        #@       #
+        # $  $ $ #
+        #        #
+        #  $  $  #
+        #        #
+        #  .....##
+        ##########
+    ";
+
+    /// 从地图字符串创建世界，同时返回求解用的棋盘
+    fn world_from(map: &str, ruleset: Ruleset) -> (World, Board) {
+        let mut world = World::new();
+        assert!(load_level_map(&mut world, map.to_string()).is_ok(), "invalid test map");
+        entity::create_ruleset(&mut world, ruleset);
+        entity::create_gameplay(&mut world);
+        entity::create_history(&mut world);
+        let board = Board::from_world(&world).unwrap();
+        (world, board)
+    }
+
+    fn cell(board: &Board, x: u8, y: u8) -> usize {
+        board.grid.index(&Position { x, y, z: 0 }).unwrap()
+    }
+
+    /// 在世界中执行解的每一步，确认每一步都与求解器的推动标记一致并最终通关
+    fn assert_solves(world: &World, moves: &[SolverMove]) {
+        for step in moves {
+            apply_action(world, step.direction);
+        }
+        let mut query = world.query::<&GamePlay>();
+        let gameplay = query.iter().next().unwrap().1;
+        assert_eq!(gameplay.state, GameplayState::Won);
+        assert_eq!(gameplay.move_log, moves.iter().map(|step| step.to_lurd()).collect::<String>());
+    }
+
+    #[test]
+    fn solves_a_corridor_under_both_rules() {
+        for ruleset in [Ruleset::Classic, Ruleset::MultiPush] {
+            let (world, board) = world_from(CORRIDOR, ruleset);
+            let solution = solve(&board, &SolverLimits::default()).unwrap();
+            assert_eq!(solution.lurd(), "RRR");
+            assert_eq!(solution.pushes, 3);
+            assert_solves(&world, &solution.moves);
+        }
+    }
+
+    #[test]
+    fn only_multi_push_moves_a_train() {
+        let (_, board) = world_from(TRAIN, Ruleset::Classic);
+        assert_eq!(solve(&board, &SolverLimits::default()).err(), Some(SolveError::Unsolvable));
+
+        let (world, board) = world_from(TRAIN, Ruleset::MultiPush);
+        let solution = solve(&board, &SolverLimits::default()).unwrap();
+        assert_eq!(solution.lurd(), "RRR");
+        assert_solves(&world, &solution.moves);
+    }
+
+    #[test]
+    fn boxes_only_count_on_goals_of_their_color() {
+        let (world, board) = world_from(COLORED, Ruleset::Classic);
+
+        // 红箱子的推动距离只计算到红目标点，蓝目标点上不是 0
+        let distances = board.push_distances();
+        assert_eq!(distances[&BoxColor::Red][cell(&board, 5, 1)], 0);
+        assert_eq!(distances[&BoxColor::Red][cell(&board, 4, 1)], 1);
+        assert_eq!(distances[&BoxColor::Blue][cell(&board, 4, 1)], 0);
+
+        // 红箱子推到红目标点（两次），蓝箱子推右再推上（两次）
+        let solution = solve(&board, &SolverLimits::default()).unwrap();
+        assert_eq!(solution.pushes, 4);
+        assert_solves(&world, &solution.moves);
+    }
+
+    #[test]
+    fn cornered_box_is_unsolvable() {
+        let (_, board) = world_from(CORNERED, Ruleset::Classic);
+        assert_eq!(board.push_distances()[&BoxColor::Blue][cell(&board, 1, 1)], UNREACHABLE);
+        assert_eq!(solve(&board, &SolverLimits::default()).err(), Some(SolveError::Unsolvable));
+    }
+
+    #[test]
+    fn node_limit_stops_the_search() {
+        let (_, board) = world_from(ROOM, Ruleset::Classic);
+        let limits = SolverLimits {
+            max_nodes: 10,
+            max_time: Duration::from_secs(60),
+        };
+        assert_eq!(solve(&board, &limits).err(), Some(SolveError::NodeLimit));
+    }
+
+    #[test]
+    fn time_limit_stops_the_search() {
+        let (_, board) = world_from(ROOM, Ruleset::Classic);
+        let limits = SolverLimits {
+            max_nodes: usize::MAX,
+            max_time: Duration::ZERO,
+        };
+        assert_eq!(solve(&board, &limits).err(), Some(SolveError::TimeLimit));
+    }
+
+    #[test]
+    fn box_path_pushes_a_single_box() {
+        let (_, board) = world_from(CORRIDOR, Ruleset::Classic);
+        let from = cell(&board, 2, 1);
+        assert_eq!(board.box_path(from, cell(&board, 5, 1)), Some(vec![Direction::Right; 3]));
+        // 不能把箱子拉回来，也不能推进墙里
+        assert_eq!(board.box_path(from, cell(&board, 1, 1)), None);
+        assert_eq!(board.box_path(from, cell(&board, 6, 1)), None);
+    }
+
+    #[test]
+    fn lurd_round_trip() {
+        for c in "lurdLURD".chars() {
+            assert_eq!(SolverMove::from_lurd(c).map(SolverMove::to_lurd), Some(c));
+        }
+        assert_eq!(SolverMove::from_lurd('R'), Some(SolverMove { direction: Direction::Right, push: true }));
+        assert_eq!(SolverMove::from_lurd('x'), None);
+    }
+}
@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+use hecs::World;
+
+use crate::{component::{ClickMove, GamePlay, GameplayState, Position}, grid::Direction, simulation::{apply_action, player_moved}, solver::Board, systems::events::Event};
+
+/// 点击移动的速度（每秒步数）
+const CLICK_MOVE_SPEED: f32 = 12.0;
+/// 点击走不到的格子时，提示显示的时长
+pub const UNREACHABLE_CUE_DURATION: Duration = Duration::from_millis(500);
+
+/// 点击棋盘上的格子（鼠标按下）
+/// 点击箱子时选中它（再次点击取消选中）；已经选中箱子时，计算只推动该箱子把它推到该格子的移动序列；
+/// 否则计算玩家不推箱子走到该格子的最短路径。移动由 `run_click_move` 逐步执行
+/// 做不到时不移动，只在该格子上显示提示；点击玩家所在的格子或地图外什么也不做
+pub fn click_tile(world: &World, target: Position) {
+    let Some((board, cell)) = playable_board(world, &target) else {
+        return;
+    };
+    let mut query = world.query::<&mut ClickMove>();
+    let Some((_, click_move)) = query.iter().next() else {
+        return;
+    };
+
+    if board.boxes().iter().any(|(box_cell, _)| *box_cell == cell) {
+        click_move.steps.clear();
+        click_move.selected_box = if click_move.selected_box == Some(target) { None } else { Some(target) };
+        return;
+    }
+
+    let path = match click_move.selected_box.take() {
+        Some(selected) => board.grid.index(&selected).and_then(|from| board.box_path(from, cell)),
+        None => board.player_path(cell),
+    };
+    start_click_move(click_move, path, target);
+}
+
+/// 松开鼠标：按住箱子拖到其他格子松开时，把箱子推到该格子
+pub fn release_tile(world: &World, target: Position) {
+    let Some((board, cell)) = playable_board(world, &target) else {
+        return;
+    };
+    let mut query = world.query::<&mut ClickMove>();
+    let Some((_, click_move)) = query.iter().next() else {
+        return;
+    };
+    let Some(selected) = click_move.selected_box.filter(|&selected| selected != target) else {
+        return;
+    };
+
+    click_move.selected_box = None;
+    let path = board.grid.index(&selected).and_then(|from| board.box_path(from, cell));
+    start_click_move(click_move, path, target);
+}
+
+/// 正在游戏中时读取当前局面和点击的格子，关卡已经结束或点击在地图外时返回 None
+fn playable_board(world: &World, target: &Position) -> Option<(Board, usize)> {
+    {
+        let mut query = world.query::<&GamePlay>();
+        if query.iter().next().is_some_and(|(_, gameplay)| gameplay.state != GameplayState::Playing) {
+            return None;
+        }
+    }
+    let board = Board::from_world(world).ok()?;
+    let cell = board.grid.index(target)?;
+    Some((board, cell))
+}
+
+/// 开始执行计算出的移动序列，没有可行的移动序列时在目标格子上显示提示
+fn start_click_move(click_move: &mut ClickMove, path: Option<Vec<Direction>>, target: Position) {
+    match path {
+        Some(path) => {
+            click_move.steps = path.into();
+            // 第一步在下一帧立即执行
+            click_move.elapsed = step_interval();
+            click_move.unreachable = None;
+        }
+        None => {
+            click_move.steps.clear();
+            click_move.unreachable = Some((target, UNREACHABLE_CUE_DURATION));
+        }
+    }
+}
+
+/// 停止正在进行的点击移动并取消选中的箱子（例如玩家改用键盘操作）
+pub fn cancel_click_move(world: &World) {
+    let mut query = world.query::<&mut ClickMove>();
+    if let Some((_, click_move)) = query.iter().next() {
+        click_move.steps.clear();
+        click_move.selected_box = None;
+    }
+}
+
+/// 点击移动系统
+/// 按固定速度沿路径逐步移动，每一步都通过 `apply_action` 执行，与键盘输入完全相同
+/// 返回这一帧执行的步骤产生的事件
+pub fn run_click_move(world: &World, ctx: &ggez::Context) -> Vec<Event> {
+    let mut query = world.query::<&mut ClickMove>();
+    let Some((_, click_move)) = query.iter().next() else {
+        return Vec::new();
+    };
+
+    let delta = ctx.time.delta();
+    if let Some((_, remaining)) = click_move.unreachable.as_mut() {
+        *remaining = remaining.saturating_sub(delta);
+        if remaining.is_zero() {
+            click_move.unreachable = None;
+        }
+    }
+    if click_move.steps.is_empty() {
+        return Vec::new();
+    }
+
+    // 帧率较低时一帧可能执行多步
+    let mut events = Vec::new();
+    click_move.elapsed += delta;
+    let interval = step_interval();
+    while click_move.elapsed >= interval {
+        click_move.elapsed -= interval;
+        let Some(direction) = click_move.steps.pop_front() else {
+            break;
+        };
+        let step_events = apply_action(world, direction);
+        let moved = player_moved(&step_events);
+        events.extend(step_events);
+        if !moved {
+            // 局面与计算路径时不同，放弃剩余的路径
+            click_move.steps.clear();
+            break;
+        }
+    }
+    events
+}
+
+/// 相邻两步之间的间隔
+fn step_interval() -> Duration {
+    Duration::from_secs_f32(1.0 / CLICK_MOVE_SPEED)
+}
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use hecs::World;
 
-use crate::component::{Box, BoxSpot, GamePlay, GameplayState, Immovable, Position, Wall};
+use crate::{component::{Box, BoxSpot, GamePlay, GameplayState, Position}, deadlock::DeadlockAnalysis};
 
 /// 核心游戏逻辑系统
 /// 检查胜利条件和失败条件（死锁）
@@ -50,44 +50,10 @@ pub fn run_gameplay_state(world: &World) {
         return;
     }
 
-    // 2. 检查失败条件（简单的死角检测）
-    // 如果任意一个箱子进入了非目标的角落，游戏失败
-    // 角落定义：(上是墙 && 左是墙) || (上 && 右) || (下 && 左) || (下 && 右)
-    
-    // 获取所有墙的位置
-    let walls: HashMap<(u8, u8), bool> = world
-        .query::<(&Position, &Wall)>()
-        .iter()
-        .map(|(_, (pos, _))| ((pos.x, pos.y), true))
-        .collect();
-
-    // 获取所有目标点位置
-    let box_spots: HashMap<(u8, u8), bool> = world
-        .query::<(&Position, &BoxSpot)>()
-        .iter()
-        .map(|(_, (pos, _))| ((pos.x, pos.y), true))
-        .collect();
-
-    // 检查每个箱子
-    for (_, (pos, _)) in world.query::<(&Position, &Box)>().iter() {
-        // 如果箱子已经在目标点上，暂时不算死（即使在角落也是胜利的一部分，或者中间状态）
-        // 这里简化：只要在任意 BoxSpot 上，就不判死。
-        if box_spots.contains_key(&(pos.x, pos.y)) {
-            continue;
-        }
-
-        let is_wall = |x, y| walls.contains_key(&(x, y));
-
-        // 检查四周是否有墙
-        let up = is_wall(pos.x, pos.y - 1);
-        let down = is_wall(pos.x, pos.y + 1);
-        let left = is_wall(pos.x - 1, pos.y);
-        let right = is_wall(pos.x + 1, pos.y);
-
-        // 如果形成了死角，判定游戏失败
-        if (up && left) || (up && right) || (down && left) || (down && right) {
-            gameplay.state = GameplayState::Lost;
-            return;
-        }
+    // 2. 检查失败条件（死锁检测）
+    // 箱子进入该颜色的死格，或者被冻结在非同色目标点上，游戏失败
+    let mut query = world.query::<&DeadlockAnalysis>();
+    if query.iter().next().is_some_and(|(_, analysis)| analysis.is_deadlocked(world)) {
+        gameplay.state = GameplayState::Lost;
     }
 }
@@ -0,0 +1,64 @@
+use hecs::World;
+
+use crate::{bindings::{Action, KeyBindings}, component::{GamePlay, GameplayState, Hint, Player, Position, SearchPurpose, SolverStatus}, grid::Grid, solver::{Solution, SolveError}, systems::search::start_search};
+
+/// 提示系统
+/// 按提示键（默认 H）在后台求解当前局面，只保留到第一次推动为止的部分作为提示
+pub fn run_hint(world: &World, ctx: &mut ggez::Context, bindings: &KeyBindings) {
+    if !bindings.is_just_pressed(ctx, Action::Hint) {
+        return;
+    }
+
+    {
+        let mut query = world.query::<&GamePlay>();
+        if query.iter().next().is_some_and(|(_, gameplay)| gameplay.state != GameplayState::Playing) {
+            return;
+        }
+    }
+
+    // 在工作线程中求解，结束后由搜索系统调用 `show_hint`
+    start_search(world, SearchPurpose::Hint);
+}
+
+/// 根据当前局面的求解结果设置提示：只保留到第一次推动为止的部分
+/// 求解失败时在右侧面板显示原因
+pub fn show_hint(world: &World, result: Result<Solution, SolveError>) {
+    let hint = match result {
+        Ok(solution) => {
+            let grid = Grid::from_world(world);
+            let mut query = world.query::<(&Position, &Player)>();
+            let mut position = *query.iter().next().unwrap().1.0;
+            let mut hint = Hint::default();
+
+            // 沿解的移动序列走到第一次推动
+            for step in solution.moves.iter() {
+                let Some(next) = grid.neighbour(&position, step.direction) else {
+                    break;
+                };
+                if step.push {
+                    hint.push = Some((next, step.direction));
+                    break;
+                }
+                hint.path.push(next);
+                position = next;
+            }
+            hint
+        }
+        Err(error) => {
+            let mut query = world.query::<&mut SolverStatus>();
+            query.iter().next().unwrap().1.message = format!("Hint: {}", error);
+            Hint::default()
+        }
+    };
+
+    let mut query = world.query::<&mut Hint>();
+    *query.iter().next().unwrap().1 = hint;
+}
+
+/// 清除提示（局面变化后提示失效）
+pub fn clear_hint(world: &World) {
+    let mut query = world.query::<&mut Hint>();
+    if let Some((_, hint)) = query.iter().next() {
+        *hint = Hint::default();
+    }
+}
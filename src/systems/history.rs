@@ -0,0 +1,86 @@
+use hecs::World;
+
+use crate::{bindings::{Action, KeyBindings}, component::{GamePlay, GameplayState, History, Position}, simulation::boxes_placed_on_spots, systems::{click_move::cancel_click_move, events::{EntityMoved, Event}, hint::clear_hint}};
+
+/// 撤销/重做系统
+/// 撤销键（默认 Z）撤销上一步，重做键（默认 Y）重做被撤销的一步
+/// 返回重做产生的事件，与正常移动一样交给音效系统
+pub fn run_history(world: &World, ctx: &mut ggez::Context, bindings: &KeyBindings) -> Vec<Event> {
+    if bindings.is_just_pressed(ctx, Action::Undo) {
+        cancel_click_move(world);
+        undo(world);
+    } else if bindings.is_just_pressed(ctx, Action::Redo) {
+        cancel_click_move(world);
+        return redo(world);
+    }
+    Vec::new()
+}
+
+/// 撤销上一步，把本步移动过的实体放回原位
+/// 返回是否有可撤销的步骤
+pub fn undo(world: &World) -> bool {
+    let record = {
+        let mut query = world.query::<&mut History>();
+        let history = query.iter().next().unwrap().1;
+        match history.undo.pop() {
+            Some(record) => record,
+            None => return false,
+        }
+    };
+
+    for (entity, from, _) in record.moves.iter() {
+        if let Ok(mut position) = world.get::<&mut Position>(*entity) {
+            *position = *from;
+        }
+    }
+
+    {
+        let mut query = world.query::<&mut GamePlay>();
+        let gameplay = query.iter().next().unwrap().1;
+        gameplay.undo_step();
+        // 撤销后局面可能不再是死局，交给 gameplay 系统重新判断
+        gameplay.state = GameplayState::Playing;
+    }
+
+    clear_hint(world);
+
+    let mut query = world.query::<&mut History>();
+    query.iter().next().unwrap().1.redo.push(record);
+    true
+}
+
+/// 重做上一次被撤销的步骤
+/// 返回这一步产生的实体移动和箱子归位事件，没有可重做的步骤时为空
+pub fn redo(world: &World) -> Vec<Event> {
+    let record = {
+        let mut query = world.query::<&mut History>();
+        let history = query.iter().next().unwrap().1;
+        match history.redo.pop() {
+            Some(record) => record,
+            None => return Vec::new(),
+        }
+    };
+
+    let mut events = Vec::new();
+    for (entity, _, to) in record.moves.iter() {
+        if let Ok(mut position) = world.get::<&mut Position>(*entity) {
+            *position = *to;
+        }
+        events.push(Event::EntityMoved(EntityMoved { entity: *entity }));
+    }
+    let placed = boxes_placed_on_spots(world, &events);
+    events.extend(placed);
+
+    {
+        let mut query = world.query::<&mut GamePlay>();
+        let gameplay = query.iter().next().unwrap().1;
+        gameplay.record_step(record.step);
+        gameplay.state = GameplayState::Playing;
+    }
+
+    clear_hint(world);
+
+    let mut query = world.query::<&mut History>();
+    query.iter().next().unwrap().1.undo.push(record);
+    events
+}
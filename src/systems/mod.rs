@@ -1,4 +1,10 @@
 pub mod input;
 pub mod rendering;
 pub mod gameplay;
-pub mod events;
\ No newline at end of file
+pub mod events;
+pub mod history;
+pub mod hint;
+pub mod replay;
+pub mod click_move;
+pub mod settings_menu;
+pub mod search;
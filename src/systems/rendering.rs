@@ -1,15 +1,14 @@
 use std::{collections::HashMap, time::Duration};
 
-use ggez::graphics::{self, Canvas, Color, DrawParam, Image, PxScale, Text, TextFragment};
+use ggez::graphics::{self, Canvas, Color, DrawMode, DrawParam, Mesh, PxScale, Rect, Text, TextFragment};
 use glam::Vec2;
-use hecs::{Entity, World};
-use itertools::Itertools;
+use hecs::World;
 
-use crate::{component::{GamePlay, GameplayState, Position, Renderable, RenderableKind, Time}, constants::TITLE_WIDTH, entity, map::LEVELS};
+use crate::{bindings::{Action, KeyBindings}, component::{ClickMove, GamePlay, GameplayState, Hint, ImageStore, Position, Renderable, Replay, Ruleset, SettingsMenu, SolverStatus, Sprite, Time}, constants::{BOARD_HEIGHT, BOARD_WIDTH, TITLE_WIDTH}, entity, grid::Grid, level_pack::LevelLibrary, map::MapError, progress::Progress, systems::click_move::UNREACHABLE_CUE_DURATION};
 
 /// 核心渲染系统
 /// 负责绘制所有游戏实体、UI 和状态提示
-pub fn run_rendering(world: &World, ctx: &mut ggez::Context, current_level_index: usize) {
+pub fn run_rendering(world: &World, ctx: &mut ggez::Context, library: &LevelLibrary, current_level_index: usize, progress: &Progress, bindings: &KeyBindings) {
     // 创建画布，设置灰色背景
     let mut canvas =
         graphics::Canvas::from_frame(ctx, graphics::Color::from([0.95, 0.95, 0.95, 1.0]));
@@ -18,52 +17,107 @@ pub fn run_rendering(world: &World, ctx: &mut ggez::Context, current_level_index
     let mut query = world.query::<&Time>();
     let time = query.iter().next().unwrap().1;
 
-    // 1. 收集所有需要渲染的实体
-    let mut query = world.query::<(&Position, &Renderable)>();
-    let mut rendering_data: Vec<(Entity, (&Position, &Renderable))> = query.into_iter().collect();
-    let mut rendering_batches: HashMap<u8, HashMap<String, Vec<DrawParam>>> = HashMap::new();
-    
-    // 按 Z 轴排序，确保正确的遮挡关系（例如箱子在目标点上面）
-    rendering_data.sort_by_key(|k| k.1.0.z);
-
-    // 2. 准备渲染批次
-    for (_, (position, renderable)) in rendering_data.iter() {
-        let image = get_image( renderable, time.delta);
-        let x = position.x as f32 * TITLE_WIDTH;
-        let y = position.y as f32 * TITLE_WIDTH;
-        let z = position.z;
-        let draw_params = DrawParam::new().dest(Vec2::new(x, y));
-        
-        // 按 Z 轴和图片路径分组，以便批量绘制
-        rendering_batches.entry(z).or_default().entry(image).or_default().push(draw_params);
-    }
+    // 根据地图尺寸计算格子大小，保证大地图也能完整显示
+    let tile_size = tile_size(&Grid::from_world(world));
 
-    // 3. 执行批量绘制
-    for (_z, group) in rendering_batches
+    // 1. 收集所有需要渲染的实体，查找每个实体当前帧对应的精灵
+    let mut image_query = world.query::<&mut ImageStore>();
+    let image_store = image_query.iter().next().unwrap().1;
+    let mut query = world.query::<(&Position, &Renderable)>();
+    let mut sprites: Vec<(&Position, Sprite)> = query
         .iter()
-        .sorted_by(|a, b| Ord::cmp(&a.0, &b.0))
-    {
-        for (image_path, draw_params) in group {
-            let image = Image::from_path(ctx, image_path).unwrap();
-            let mut mesh_batch = graphics::InstanceArray::new(ctx, Some(image));
+        .map(|(_, (position, renderable))| {
+            let frame = get_frame(image_store.theme.frames(renderable.tile), time.delta);
+            (position, image_store.sprite(ctx, &frame))
+        })
+        .collect();
+
+    // 按 Z 轴排序，确保正确的遮挡关系（例如箱子在目标点上面）；同一层内按贴图排序，减少批次
+    sprites.sort_by(|a, b| a.0.z.cmp(&b.0.z).then_with(|| a.1.texture.cmp(&b.1.texture)));
 
-            for draw_param in draw_params.iter() {
-                mesh_batch.push(*draw_param);
-            }
+    // 2. 准备渲染批次：按绘制顺序把使用同一贴图的连续精灵合为一批
+    // 使用精灵图集时整个棋盘只有一个批次
+    let mut rendering_batches: Vec<(String, Vec<DrawParam>)> = Vec::new();
+    for (position, sprite) in sprites {
+        let x = position.x as f32 * tile_size;
+        let y = position.y as f32 * tile_size;
+        let scale = tile_size / sprite.width;
+        let draw_param = DrawParam::new().src(sprite.src).dest(Vec2::new(x, y)).scale(Vec2::new(scale, scale));
 
-            canvas.draw(&mesh_batch, graphics::DrawParam::new());
+        match rendering_batches.last_mut() {
+            Some((texture, draw_params)) if *texture == sprite.texture => draw_params.push(draw_param),
+            _ => rendering_batches.push((sprite.texture, vec![draw_param])),
         }
     }
 
-    // 4. 绘制 UI 信息（步数、FPS）
+    // 3. 执行批量绘制，贴图和批量绘制数组都来自缓存，每帧只替换实例
+    let mut texture_batches: HashMap<&str, usize> = HashMap::new();
+    for (texture, draw_params) in rendering_batches.iter() {
+        let index = texture_batches.entry(texture).or_default();
+        let mesh_batch = image_store.batch(ctx, texture, *index);
+        *index += 1;
+        mesh_batch.set(draw_params.iter().copied());
+        canvas.draw(mesh_batch, graphics::DrawParam::new());
+    }
+
+    // 绘制提示：玩家路径用小方块标出，要推的箱子和推动方向用边框标出
+    let mut query = world.query::<&Hint>();
+    let hint = query.iter().next().unwrap().1;
+    draw_hint(&mut canvas, ctx, hint, tile_size);
+
+    // 选中的箱子用蓝框标出；点击了做不到的目的地时，在该格子上短暂显示逐渐消失的红框
+    let mut query = world.query::<&ClickMove>();
+    if let Some((_, click_move)) = query.iter().next() {
+        draw_selected_box(&mut canvas, ctx, click_move, tile_size);
+        draw_unreachable_cue(&mut canvas, ctx, click_move, tile_size);
+    }
+
+    // 4. 绘制 UI 信息（步数、推动次数、FPS）
     let mut query = world.query::<&GamePlay>();
     let gameplay = query.iter().next().unwrap().1;
     let fps = format!("FPS: {:.0}", ctx.time.fps());
     
+    // 在右侧显示当前关卡包和关卡标题
+    if let Some((pack, level)) = library.get(current_level_index) {
+        draw_text(&mut canvas, &truncate_text(&pack.title, 13), 525.0, 20.0, Color::new(0.3, 0.3, 0.3, 1.0));
+        draw_text(&mut canvas, &truncate_text(&library.display_name(current_level_index), 13), 525.0, 40.0, Color::new(0.0, 0.0, 0.0, 1.0));
+
+        // 在左下角显示关卡说明
+        for (i, note) in level.notes.iter().enumerate() {
+            draw_text(&mut canvas, note, 20.0, 500.0 + i as f32 * 22.0, Color::new(0.3, 0.3, 0.3, 1.0));
+        }
+    }
+
+    // 显示本关使用的规则
+    let mut query = world.query::<&Ruleset>();
+    if let Some((_, ruleset)) = query.iter().next() {
+        draw_text(&mut canvas, &format!("Rules: {}", ruleset), 525.0, 60.0, Color::new(0.3, 0.3, 0.3, 1.0));
+    }
+
     // 在右侧显示状态信息
     draw_text(&mut canvas, &gameplay.state.to_string(), 525.0, 80.0, Color::new(0.0, 0.0, 0.0, 1.0));
-    draw_text(&mut canvas, &format!("Moves: {}", gameplay.move_count), 525.0, 100.0, Color::new(0.0, 0.0, 0.0, 1.0));
-    draw_text(&mut canvas, &fps, 525.0, 120.0, Color::new(0.0, 0.0, 0.0, 1.0));
+    draw_text(&mut canvas, &fps, 675.0, 80.0, Color::new(0.3, 0.3, 0.3, 1.0));
+    let counts = format!("Moves: {}  Pushes: {}", gameplay.move_count, gameplay.push_count);
+    draw_text(&mut canvas, &counts, 525.0, 100.0, Color::new(0.0, 0.0, 0.0, 1.0));
+    let lines = format!("Lines: box {}  player {}", gameplay.box_lines, gameplay.player_lines);
+    draw_text(&mut canvas, &lines, 525.0, 120.0, Color::new(0.3, 0.3, 0.3, 1.0));
+
+    // 回放模式下显示回放进度，否则显示最近一次求解结果
+    let mut query = world.query::<&Replay>();
+    if let Some((_, replay)) = query.iter().next() {
+        let progress = format!(
+            "Replay {}/{} {}/s{}",
+            replay.position,
+            replay.moves.len(),
+            replay.speed,
+            if replay.paused { " paused" } else { "" }
+        );
+        draw_text(&mut canvas, &progress, 525.0, 140.0, Color::new(0.0, 0.0, 1.0, 1.0));
+    } else {
+        let mut query = world.query::<&SolverStatus>();
+        let solver_status = query.iter().next().unwrap().1;
+        draw_text(&mut canvas, &solver_status.message, 525.0, 140.0, Color::new(0.3, 0.3, 0.3, 1.0));
+    }
 
     // 绘制关卡列表
     draw_text(&mut canvas, "Level Select:", 525.0, 160.0, Color::new(0.0, 0.0, 0.0, 1.0));
@@ -76,12 +130,13 @@ pub fn run_rendering(world: &World, ctx: &mut ggez::Context, current_level_index
     } else {
         0
     };
-    let start_index = if start_index + visible_count > LEVELS.len() {
-        LEVELS.len().saturating_sub(visible_count)
+    let level_count = library.len();
+    let start_index = if start_index + visible_count > level_count {
+        level_count.saturating_sub(visible_count)
     } else {
         start_index
     };
-    let end_index = std::cmp::min(start_index + visible_count, LEVELS.len());
+    let end_index = std::cmp::min(start_index + visible_count, level_count);
 
     for i in start_index..end_index {
         let display_index = i - start_index;
@@ -91,26 +146,161 @@ pub fn run_rendering(world: &World, ctx: &mut ggez::Context, current_level_index
         } else {
             Color::new(0.4, 0.4, 0.4, 1.0) // 未选中灰色
         };
-        draw_text(&mut canvas, &format!("Level {}", i + 1), 540.0, y, color);
+        let label = format!("{}. {}", i + 1, library.display_name(i));
+        draw_text(&mut canvas, &truncate_text(&label, 13), 540.0, y, color);
+
+        // 已通关的关卡显示对勾和最佳记录（步数/推动次数）
+        if let Some(record) = progress.get(&library.level_key(i)) {
+            draw_checkmark(&mut canvas, ctx, 526.0, y + 4.0);
+            let best = format!("{}/{}", record.best_moves.moves, record.best_pushes.pushes);
+            draw_text(&mut canvas, &best, 705.0, y, Color::new(0.0, 0.5, 0.0, 1.0));
+        }
+    }
+
+    // 关卡地图有误时在棋盘区域显示错误信息
+    let mut query = world.query::<&MapError>();
+    if let Some((_, error)) = query.iter().next() {
+        draw_map_error(&mut canvas, error, bindings);
     }
 
     // 5. 绘制游戏结束状态提示
     match gameplay.state {
         GameplayState::Won => {
-            let win_text = "You Won!\nPress Enter to Next Level";
-            draw_center_text(&mut canvas, ctx, win_text, Color::from([0.0, 0.8, 0.0, 1.0]));
+            let win_text = format!("You Won!\nPress {} to Next Level", bindings.key_name(Action::NextLevel));
+            draw_center_text(&mut canvas, ctx, &win_text, Color::from([0.0, 0.8, 0.0, 1.0]));
         }
         GameplayState::Lost => {
-            let lost_text = "Game Over!\nPress R to Restart";
-            draw_center_text(&mut canvas, ctx, lost_text, Color::from([0.8, 0.0, 0.0, 1.0]));
+            let lost_text = format!(
+                "Game Over!\nPress {} to Restart\nor {} to Undo",
+                bindings.key_name(Action::Restart),
+                bindings.key_name(Action::Undo)
+            );
+            draw_center_text(&mut canvas, ctx, &lost_text, Color::from([0.8, 0.0, 0.0, 1.0]));
         }
         _ => {}
     }
 
+    // 设置菜单打开时覆盖在棋盘上方
+    let mut query = world.query::<&SettingsMenu>();
+    if let Some((_, menu)) = query.iter().next() {
+        draw_settings_menu(&mut canvas, ctx, menu, &image_store.theme.id, bindings);
+    }
+
     // 提交绘制命令
     canvas.finish(ctx).expect("expected to present");
 }
 
+/// 计算格子的绘制尺寸
+/// 地图能放下时使用原始尺寸，否则缩小到刚好填满棋盘区域
+pub fn tile_size(grid: &Grid) -> f32 {
+    let width = grid.width.max(1) as f32;
+    let height = grid.height.max(1) as f32;
+    TITLE_WIDTH.min(BOARD_WIDTH / width).min(BOARD_HEIGHT / height)
+}
+
+/// 绘制提示覆盖层
+pub fn draw_hint(canvas: &mut Canvas, ctx: &ggez::Context, hint: &Hint, tile_size: f32) {
+    let marker = tile_size / 3.0;
+    for position in hint.path.iter() {
+        let rect = Rect::new(
+            position.x as f32 * tile_size + marker,
+            position.y as f32 * tile_size + marker,
+            marker,
+            marker,
+        );
+        let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, Color::new(1.0, 0.8, 0.0, 0.8)).unwrap();
+        canvas.draw(&mesh, DrawParam::new());
+    }
+
+    if let Some((position, direction)) = hint.push {
+        let (dx, dy) = direction.delta();
+        let cells = [
+            ((position.x as f32, position.y as f32), Color::new(0.0, 0.7, 0.0, 1.0)),
+            ((position.x as f32 + dx as f32, position.y as f32 + dy as f32), Color::new(0.0, 0.7, 0.0, 0.4)),
+        ];
+        for ((x, y), color) in cells {
+            let rect = Rect::new(x * tile_size, y * tile_size, tile_size, tile_size);
+            let mesh = Mesh::new_rectangle(ctx, DrawMode::stroke(3.0), rect, color).unwrap();
+            canvas.draw(&mesh, DrawParam::new());
+        }
+    }
+}
+
+/// 绘制关卡错误界面：每个问题一行，最后提示如何跳过
+pub fn draw_map_error(canvas: &mut Canvas, error: &MapError, bindings: &KeyBindings) {
+    draw_text(canvas, "This level cannot be loaded:", 20.0, 20.0, Color::new(0.8, 0.0, 0.0, 1.0));
+    let messages = error.messages();
+    for (i, message) in messages.iter().enumerate() {
+        draw_text(canvas, message, 20.0, 50.0 + i as f32 * 24.0, Color::new(0.3, 0.0, 0.0, 1.0));
+    }
+    let y = 70.0 + messages.len() as f32 * 24.0;
+    let skip = format!("Press {} for the next level", bindings.key_name(Action::NextLevel));
+    draw_text(canvas, &skip, 20.0, y, Color::new(0.3, 0.3, 0.3, 1.0));
+}
+
+/// 绘制设置菜单：主题列表，高亮的主题为蓝色，当前使用的主题带有标记
+pub fn draw_settings_menu(canvas: &mut Canvas, ctx: &ggez::Context, menu: &SettingsMenu, current_theme: &str, bindings: &KeyBindings) {
+    let height = 120.0 + menu.themes.len() as f32 * 30.0;
+    let rect = Rect::new(40.0, 40.0, BOARD_WIDTH - 80.0, height);
+    let background = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, Color::new(1.0, 1.0, 1.0, 0.95)).unwrap();
+    canvas.draw(&background, DrawParam::new());
+    let border = Mesh::new_rectangle(ctx, DrawMode::stroke(2.0), rect, Color::new(0.3, 0.3, 0.3, 1.0)).unwrap();
+    canvas.draw(&border, DrawParam::new());
+
+    draw_text(canvas, "Settings", 60.0, 55.0, Color::new(0.0, 0.0, 0.0, 1.0));
+    draw_text(canvas, "Theme:", 60.0, 85.0, Color::new(0.3, 0.3, 0.3, 1.0));
+    for (i, (id, name)) in menu.themes.iter().enumerate() {
+        let color = if i == menu.selected {
+            Color::new(0.0, 0.0, 1.0, 1.0)
+        } else {
+            Color::new(0.4, 0.4, 0.4, 1.0)
+        };
+        let marker = if id == current_theme { " (current)" } else { "" };
+        draw_text(canvas, &format!("{}{}", name, marker), 80.0, 115.0 + i as f32 * 30.0, color);
+    }
+
+    let help = format!(
+        "{}/{}: choose  {}: apply  {}: close",
+        bindings.key_name(Action::MoveUp),
+        bindings.key_name(Action::MoveDown),
+        bindings.key_name(Action::NextLevel),
+        bindings.key_name(Action::Settings)
+    );
+    draw_text(canvas, &help, 60.0, 125.0 + menu.themes.len() as f32 * 30.0, Color::new(0.3, 0.3, 0.3, 1.0));
+}
+
+/// 绘制选中箱子的边框
+pub fn draw_selected_box(canvas: &mut Canvas, ctx: &ggez::Context, click_move: &ClickMove, tile_size: f32) {
+    let Some(position) = click_move.selected_box else {
+        return;
+    };
+    let rect = Rect::new(position.x as f32 * tile_size, position.y as f32 * tile_size, tile_size, tile_size);
+    let mesh = Mesh::new_rectangle(ctx, DrawMode::stroke(3.0), rect, Color::new(0.0, 0.4, 1.0, 1.0)).unwrap();
+    canvas.draw(&mesh, DrawParam::new());
+}
+
+/// 绘制点击做不到的目的地时的提示
+pub fn draw_unreachable_cue(canvas: &mut Canvas, ctx: &ggez::Context, click_move: &ClickMove, tile_size: f32) {
+    let Some((position, remaining)) = click_move.unreachable else {
+        return;
+    };
+    let alpha = remaining.as_secs_f32() / UNREACHABLE_CUE_DURATION.as_secs_f32();
+    let rect = Rect::new(position.x as f32 * tile_size, position.y as f32 * tile_size, tile_size, tile_size);
+    let mesh = Mesh::new_rectangle(ctx, DrawMode::stroke(3.0), rect, Color::new(0.9, 0.0, 0.0, alpha)).unwrap();
+    canvas.draw(&mesh, DrawParam::new());
+}
+
+/// 绘制已通关关卡的对勾，(x, y) 为对勾左上角
+pub fn draw_checkmark(canvas: &mut Canvas, ctx: &ggez::Context, x: f32, y: f32) {
+    let points = [
+        Vec2::new(x, y + 6.0),
+        Vec2::new(x + 4.0, y + 11.0),
+        Vec2::new(x + 11.0, y),
+    ];
+    let mesh = Mesh::new_line(ctx, &points, 2.5, Color::new(0.0, 0.6, 0.0, 1.0)).unwrap();
+    canvas.draw(&mesh, DrawParam::new());
+}
+
 /// 绘制普通文本
 pub fn draw_text(canvas: &mut Canvas,text_str: &str, x: f32, y: f32, color: Color){
     let text = Text::new(TextFragment {
@@ -123,6 +313,17 @@ pub fn draw_text(canvas: &mut Canvas,text_str: &str, x: f32, y: f32, color: Colo
     canvas.draw(&text, Vec2::new(x, y));
 }
 
+/// 截断过长的文本，避免超出右侧面板
+pub fn truncate_text(text_str: &str, max_chars: usize) -> String {
+    if text_str.chars().count() <= max_chars {
+        text_str.to_string()
+    } else {
+        let mut truncated: String = text_str.chars().take(max_chars.saturating_sub(2)).collect();
+        truncated.push_str("..");
+        truncated
+    }
+}
+
 /// 绘制屏幕居中文本（用于胜利/失败提示）
 pub fn draw_center_text(canvas: &mut Canvas, ctx: &ggez::Context, text_str: &str, color: Color) {
     let mut text = Text::new(TextFragment {
@@ -142,17 +343,14 @@ pub fn draw_center_text(canvas: &mut Canvas, ctx: &ggez::Context, text_str: &str
     canvas.draw(&text, Vec2::new(x, y));
 }
 
-/// 获取当前需要渲染的图片路径（支持动画）
-pub fn get_image(renderable: &Renderable,delta: Duration) -> String{
-    let path_index= match renderable.kind() {
-        RenderableKind::Static => {
-            0
-        }
-        RenderableKind::Animated => {
-            // 每 250ms 切换一帧
-            ((delta.as_millis()% 1000)/250) as usize
-        }
+/// 从图块的帧中选出当前需要渲染的帧名（支持动画）
+pub fn get_frame(frames: &[String],delta: Duration) -> String{
+    let frame_index= match frames.len() {
+        0 => panic!("Invalid renderable frames"),
+        1 => 0,
+        // 每 250ms 切换一帧
+        _ => ((delta.as_millis()% 1000)/250) as usize,
     };
 
-    renderable.path(path_index)
+    frames[frame_index % frames.len()].clone()
 }
@@ -0,0 +1,115 @@
+use std::time::Duration;
+
+use ggez::input::keyboard::KeyCode;
+use hecs::World;
+
+use crate::{component::{Replay, SolverStatus}, simulation::{apply_action, player_moved}, systems::{events::Event, history::undo}};
+
+/// 默认回放速度（每秒步数）
+pub const DEFAULT_REPLAY_SPEED: f32 = 8.0;
+/// 回放速度范围
+const MIN_REPLAY_SPEED: f32 = 1.0;
+const MAX_REPLAY_SPEED: f32 = 64.0;
+/// PageUp / PageDown 一次跳过的步数
+const SEEK_STEPS: usize = 10;
+
+/// 当前是否处于回放模式
+pub fn is_replaying(world: &World) -> bool {
+    world.query::<&Replay>().iter().next().is_some()
+}
+
+/// 回放系统
+/// 空格暂停/继续，左右方向键单步后退/前进，上下方向键加速/减速，
+/// PageUp / PageDown 后退/前进若干步，Home / End 跳到开头/结尾
+/// 返回向前执行的步骤产生的事件
+pub fn run_replay(world: &World, ctx: &mut ggez::Context) -> Vec<Event> {
+    let mut query = world.query::<&mut Replay>();
+    let Some((_, replay)) = query.iter().next() else {
+        return Vec::new();
+    };
+
+    if ctx.keyboard.is_key_just_pressed(KeyCode::Space) {
+        replay.paused = !replay.paused;
+        // 在结尾处继续播放时从头开始
+        if !replay.paused && replay.position == replay.moves.len() {
+            seek(world, replay, 0);
+        }
+    }
+    if ctx.keyboard.is_key_just_pressed(KeyCode::Up) {
+        replay.speed = (replay.speed * 2.0).min(MAX_REPLAY_SPEED);
+    }
+    if ctx.keyboard.is_key_just_pressed(KeyCode::Down) {
+        replay.speed = (replay.speed / 2.0).max(MIN_REPLAY_SPEED);
+    }
+
+    // 手动定位会暂停自动播放
+    let target = if ctx.keyboard.is_key_just_pressed(KeyCode::Right) {
+        Some(replay.position + 1)
+    } else if ctx.keyboard.is_key_just_pressed(KeyCode::Left) {
+        Some(replay.position.saturating_sub(1))
+    } else if ctx.keyboard.is_key_just_pressed(KeyCode::PageDown) {
+        Some(replay.position + SEEK_STEPS)
+    } else if ctx.keyboard.is_key_just_pressed(KeyCode::PageUp) {
+        Some(replay.position.saturating_sub(SEEK_STEPS))
+    } else if ctx.keyboard.is_key_just_pressed(KeyCode::Home) {
+        Some(0)
+    } else if ctx.keyboard.is_key_just_pressed(KeyCode::End) {
+        Some(replay.moves.len())
+    } else {
+        None
+    };
+    if let Some(target) = target {
+        replay.paused = true;
+        replay.elapsed = Duration::ZERO;
+        return seek(world, replay, target);
+    }
+
+    if replay.paused {
+        return Vec::new();
+    }
+
+    // 按速度自动前进，帧率较低时一帧可能执行多步
+    replay.elapsed += ctx.time.delta();
+    let interval = Duration::from_secs_f32(1.0 / replay.speed);
+    let mut target = replay.position;
+    while replay.elapsed >= interval && target < replay.moves.len() {
+        replay.elapsed -= interval;
+        target += 1;
+    }
+    let events = seek(world, replay, target);
+
+    // 播放到结尾后自动暂停
+    if replay.position == replay.moves.len() {
+        replay.paused = true;
+        replay.elapsed = Duration::ZERO;
+    }
+    events
+}
+
+/// 跳到回放的指定步数，返回向前执行的步骤产生的事件
+/// 向前通过 `apply_action` 执行，与键盘输入完全相同；向后通过撤销恢复
+fn seek(world: &World, replay: &mut Replay, target: usize) -> Vec<Event> {
+    let target = target.min(replay.moves.len());
+    let mut events = Vec::new();
+
+    while replay.position < target {
+        let step = replay.moves[replay.position];
+        let step_events = apply_action(world, step.direction);
+        if !player_moved(&step_events) {
+            // 解法与关卡不符，丢弃这一步之后的部分
+            let mut query = world.query::<&mut SolverStatus>();
+            if let Some((_, status)) = query.iter().next() {
+                status.message = format!("Replay: step {} is blocked", replay.position + 1);
+            }
+            replay.moves.truncate(replay.position);
+            break;
+        }
+        events.extend(step_events);
+        replay.position += 1;
+    }
+
+    while replay.position > target && undo(world) {
+        replay.position -= 1;
+    }
+    events
+}
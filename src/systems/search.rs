@@ -0,0 +1,83 @@
+use std::thread;
+
+use hecs::World;
+
+use crate::{
+    component::{GamePlay, Search, SearchPurpose, SearchTask, SolverStatus},
+    solver::{solve, Board, SolverLimits},
+    systems::hint::show_hint,
+};
+
+/// 在工作线程中搜索当前局面，搜索期间右侧面板显示 "Searching..."
+/// 已经有搜索在进行时忽略新的请求
+pub fn start_search(world: &World, purpose: SearchPurpose) {
+    {
+        let mut query = world.query::<&Search>();
+        if query.iter().next().is_none_or(|(_, search)| search.task.is_some()) {
+            return;
+        }
+    }
+
+    let board = Board::from_world(world);
+    let task = SearchTask {
+        purpose,
+        move_log: move_log(world),
+        handle: thread::spawn(move || solve(&board?, &SolverLimits::default())),
+    };
+    {
+        let mut query = world.query::<&mut Search>();
+        query.iter().next().unwrap().1.task = Some(task);
+    }
+    set_status(world, "Searching...".to_string());
+}
+
+/// 后台搜索系统：每帧检查搜索是否结束，结束时按用途显示提示或求解结果
+/// 搜索期间局面发生了变化（移动、撤销等）时结果已经过时，直接丢弃
+pub fn run_search(world: &World) {
+    let task = {
+        let mut query = world.query::<&mut Search>();
+        let Some((_, search)) = query.iter().next() else {
+            return;
+        };
+        if !search.task.as_ref().is_some_and(|task| task.handle.is_finished()) {
+            return;
+        }
+        search.task.take().unwrap()
+    };
+
+    let Ok(result) = task.handle.join() else {
+        set_status(world, "Search failed".to_string());
+        return;
+    };
+    if move_log(world) != task.move_log {
+        set_status(world, String::new());
+        return;
+    }
+
+    match task.purpose {
+        SearchPurpose::Hint => {
+            set_status(world, String::new());
+            show_hint(world, result);
+        }
+        SearchPurpose::Solve => {
+            let message = match result {
+                Ok(solution) => format!("Solver: {} pushes, {} moves", solution.pushes, solution.moves.len()),
+                Err(error) => format!("Solver: {}", error),
+            };
+            set_status(world, message);
+        }
+    }
+}
+
+/// 当前的移动记录，用于判断局面是否变化
+fn move_log(world: &World) -> String {
+    let mut query = world.query::<&GamePlay>();
+    query.iter().next().map(|(_, gameplay)| gameplay.move_log.clone()).unwrap_or_default()
+}
+
+fn set_status(world: &World, message: String) {
+    let mut query = world.query::<&mut SolverStatus>();
+    if let Some((_, status)) = query.iter().next() {
+        status.message = message;
+    }
+}
@@ -0,0 +1,47 @@
+use hecs::World;
+
+use crate::{bindings::{Action, KeyBindings}, component::SettingsMenu};
+
+/// 设置菜单中玩家的操作
+pub enum MenuCommand {
+    /// 菜单保持打开
+    Stay,
+    /// 关闭菜单，不做修改
+    Close,
+    /// 使用选中的主题（目录名）并关闭菜单
+    ApplyTheme(String),
+}
+
+/// 设置菜单是否打开
+pub fn is_settings_open(world: &World) -> bool {
+    world.query::<&SettingsMenu>().iter().next().is_some()
+}
+
+/// 设置菜单系统
+/// 上下移动键选择主题，下一关键（默认回车）确认，撤销键或设置键（默认 F2）关闭
+pub fn run_settings_menu(world: &World, ctx: &ggez::Context, bindings: &KeyBindings) -> MenuCommand {
+    let mut query = world.query::<&mut SettingsMenu>();
+    let Some((_, menu)) = query.iter().next() else {
+        return MenuCommand::Stay;
+    };
+
+    if bindings.is_just_pressed(ctx, Action::Settings) || bindings.is_just_pressed(ctx, Action::Undo) {
+        return MenuCommand::Close;
+    }
+    if menu.themes.is_empty() {
+        return MenuCommand::Stay;
+    }
+    if bindings.is_just_pressed(ctx, Action::NextLevel) {
+        return MenuCommand::ApplyTheme(menu.themes[menu.selected].0.clone());
+    }
+
+    // 选择在列表两端循环
+    let count = menu.themes.len();
+    if bindings.is_just_pressed(ctx, Action::MoveUp) {
+        menu.selected = (menu.selected + count - 1) % count;
+    }
+    if bindings.is_just_pressed(ctx, Action::MoveDown) {
+        menu.selected = (menu.selected + 1) % count;
+    }
+    MenuCommand::Stay
+}
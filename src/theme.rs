@@ -0,0 +1,165 @@
+use std::{collections::{BTreeMap, HashMap}, io::Read};
+
+use serde::Deserialize;
+
+use crate::{atlas::Atlas, component::Tile};
+
+/// 主题所在的资源目录，每个子目录是一个主题
+pub const THEME_DIR: &str = "/themes";
+/// 默认主题（目录名）
+pub const DEFAULT_THEME: &str = "classic";
+/// 主题目录中的清单文件名
+const THEME_MANIFEST: &str = "theme.toml";
+/// 主题目录中默认的图集清单文件名
+const DEFAULT_ATLAS: &str = "atlas.toml";
+
+/// 主题清单的内容：显示名称、图集清单，以及图块到帧名的映射（键为 `Tile::name`）
+/// ```toml
+/// name = "Night"
+///
+/// [tiles]
+/// wall = ["wall"]
+/// box_red = ["box_red_1", "box_red_2"]
+/// ```
+#[derive(Deserialize)]
+struct ThemeFile {
+    #[serde(default)]
+    name: String,
+    /// 图集清单（相对于主题目录），默认为 `atlas.toml`
+    #[serde(default)]
+    atlas: Option<String>,
+    #[serde(default)]
+    tiles: BTreeMap<String, Vec<String>>,
+}
+
+/// 视觉主题：每种图块使用哪些帧，以及到哪里查找这些帧
+/// 帧先在主题的图集中查找，图集中没有时从主题目录下的 `<帧名>.png` 单独加载
+#[derive(Clone)]
+pub struct Theme {
+    /// 主题目录名，用于记住玩家的选择
+    pub id: String,
+    /// 显示名称
+    pub name: String,
+    /// 图集清单在资源目录中的路径（文件可以不存在）
+    pub atlas: String,
+    tiles: HashMap<Tile, Vec<String>>,
+}
+
+impl Default for Theme {
+    /// 没有任何主题清单时使用的主题：默认主题目录和默认帧名
+    fn default() -> Self {
+        Theme {
+            id: DEFAULT_THEME.to_string(),
+            name: DEFAULT_THEME.to_string(),
+            atlas: format!("{}/{}/{}", THEME_DIR, DEFAULT_THEME, DEFAULT_ATLAS),
+            tiles: default_tiles(),
+        }
+    }
+}
+
+impl Theme {
+    /// 读取主题目录中的清单，没有该主题时返回 None
+    /// 清单损坏时只打印提示，同样返回 None；无法识别的图块名单独忽略
+    pub fn load(ctx: &ggez::Context, id: &str) -> Option<Theme> {
+        let dir = format!("{}/{}", THEME_DIR, id);
+        let path = format!("{}/{}", dir, THEME_MANIFEST);
+        let mut text = String::new();
+        ctx.fs.open(&path).ok()?.read_to_string(&mut text).ok()?;
+        let file: ThemeFile = match toml::from_str(&text) {
+            Ok(file) => file,
+            Err(error) => {
+                println!("ignoring corrupt theme manifest {}: {}", path, error);
+                return None;
+            }
+        };
+
+        let mut tiles = default_tiles();
+        for (tile_name, frames) in file.tiles {
+            match Tile::parse(&tile_name) {
+                Some(_) if frames.is_empty() => println!("ignoring tile '{}' without frames in theme {}", tile_name, id),
+                Some(tile) => {
+                    tiles.insert(tile, frames);
+                }
+                None => println!("ignoring unknown tile '{}' in theme {}", tile_name, id),
+            }
+        }
+        Some(Theme {
+            id: id.to_string(),
+            name: if file.name.is_empty() { id.to_string() } else { file.name },
+            atlas: format!("{}/{}", dir, file.atlas.as_deref().unwrap_or(DEFAULT_ATLAS)),
+            tiles,
+        })
+    }
+
+    /// 读取指定主题，找不到时依次退回默认主题和内置的默认设置
+    /// 主题缺少帧时在这里打印一次提示，渲染时不再提示
+    pub fn load_or_default(ctx: &ggez::Context, id: &str) -> Theme {
+        let theme = Theme::load(ctx, id)
+            .or_else(|| {
+                println!("theme '{}' not found, using {}", id, DEFAULT_THEME);
+                Theme::load(ctx, DEFAULT_THEME)
+            })
+            .unwrap_or_default();
+        theme.warn_missing_frames(ctx);
+        theme
+    }
+
+    /// 检查主题用到的所有帧，打印图集和单独的图片中都没有的帧
+    /// 渲染时这些帧改用默认主题的同名帧，默认主题也没有时绘制占位块
+    fn warn_missing_frames(&self, ctx: &ggez::Context) {
+        let mut frames: Vec<&String> = Tile::ALL.into_iter().flat_map(|tile| self.frames(tile)).collect();
+        frames.sort();
+        frames.dedup();
+        let missing = self.missing_frames(ctx, frames);
+        if missing.is_empty() {
+            return;
+        }
+        if self.id != DEFAULT_THEME {
+            println!("theme {} is missing frames {}, using {}", self.id, missing.join(", "), DEFAULT_THEME);
+        }
+        let placeholders = Theme::default().missing_frames(ctx, &missing);
+        if !placeholders.is_empty() {
+            println!("frames {} not found in {} either, drawing placeholders", placeholders.join(", "), DEFAULT_THEME);
+        }
+    }
+
+    /// 在主题的图集和单独的图片中都找不到的帧
+    fn missing_frames<'a>(&self, ctx: &ggez::Context, frames: impl IntoIterator<Item = &'a String>) -> Vec<String> {
+        let atlas = Atlas::load(ctx, &self.atlas).filter(|atlas| ctx.fs.exists(&atlas.image));
+        frames
+            .into_iter()
+            .filter(|frame| {
+                atlas.as_ref().is_none_or(|atlas| atlas.frame(frame).is_none()) && !ctx.fs.exists(self.frame_image_path(frame))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// 图块的动画帧，清单中没有指定时为默认帧名
+    pub fn frames(&self, tile: Tile) -> &[String] {
+        self.tiles.get(&tile).map_or(&[], |frames| frames.as_slice())
+    }
+
+    /// 单独加载的帧对应的图片路径，例如 `player_1` 对应 `/themes/classic/player_1.png`
+    pub fn frame_image_path(&self, frame: &str) -> String {
+        format!("{}/{}/{}.png", THEME_DIR, self.id, frame)
+    }
+}
+
+/// 所有图块的默认帧名
+fn default_tiles() -> HashMap<Tile, Vec<String>> {
+    Tile::ALL.into_iter().map(|tile| (tile, tile.default_frames())).collect()
+}
+
+/// 列出所有已安装的主题，按目录名排序
+pub fn list_themes(ctx: &ggez::Context) -> Vec<Theme> {
+    let Ok(dirs) = ctx.fs.read_dir(THEME_DIR) else {
+        return Vec::new();
+    };
+    let mut ids: Vec<String> = dirs
+        .filter_map(|dir| Some(dir.file_name()?.to_string_lossy().to_string()))
+        .collect();
+    ids.sort();
+    ids.dedup();
+    ids.iter().filter_map(|id| Theme::load(ctx, id)).collect()
+}
@@ -1,43 +1,353 @@
 use ggez::{
-    input::{keyboard::KeyCode, mouse::MouseButton}, GameResult, event,
+    event::{Axis, Button, GamepadId}, input::{keyboard::KeyCode, mouse::MouseButton}, GameResult, event,
 };
 use hecs::{ World};
 
-use crate::{component::{GameplayState, GamePlay, Time}, map::{initialize_level, LEVELS}, systems::{gameplay::run_gameplay_state, input::run_input, rendering::run_rendering}};
+use crate::{bindings::{Action, KeyBindings}, component::{GameplayState, GamePlay, Position, Replay, Ruleset, SearchPurpose, SettingsMenu, SolverStatus, Time}, constants::{BOARD_HEIGHT, BOARD_WIDTH}, entity::{create_replay, create_settings_menu, load_images}, grid::Grid, level_pack::LevelLibrary, map::{initialize_level, MapError}, progress::{Progress, Score}, savegame::SavedGame, settings::Settings, solution::{apply_solution, export_solution, import_solution}, systems::{click_move::{click_tile, release_tile, run_click_move}, events::play_sounds, gameplay::run_gameplay_state, hint::run_hint, history::run_history, input::run_input, rendering::{run_rendering, tile_size}, replay::{is_replaying, run_replay}, search::{run_search, start_search}, settings_menu::{is_settings_open, run_settings_menu, MenuCommand}}, theme::{list_themes, Theme}};
 
 /// 游戏主结构体，维护游戏世界和当前关卡状态
 pub struct Game {
     /// ECS 世界，存储所有实体和组件
     pub world: World,
-    /// 当前关卡索引
+    /// 已安装的所有关卡包
+    pub library: LevelLibrary,
+    /// 当前关卡索引（在关卡库中的全局编号）
     pub current_level: usize,
+    /// 全局默认规则，关卡或关卡包没有指定规则时使用
+    pub ruleset: Ruleset,
+    /// 玩家进度（已通关的关卡和最佳记录），保存在用户数据目录
+    pub progress: Progress,
+    /// 按键绑定，从用户数据目录的配置文件读取
+    pub bindings: KeyBindings,
+    /// 设置菜单中的选择，保存在用户数据目录
+    pub settings: Settings,
+    /// 当前使用的视觉主题
+    pub theme: Theme,
+    /// 上一帧的游戏状态，用于检测刚刚通关的时刻
+    last_state: GameplayState,
+    /// 当前局面是否由导入或回放的解法文件得到，重新加载或重新开始关卡之前不计入成绩
+    imported: bool,
 }
 
 impl Game {
     /// 创建新的游戏实例
-    /// 初始化 ECS 世界并加载第一个关卡
+    /// 加载关卡包、玩家进度、按键配置和设置，初始化 ECS 世界并加载上次游玩的关卡
+    /// 上次关闭窗口时有未完成的关卡，则恢复当时的局面
     pub fn new(ctx: &mut ggez::Context) -> GameResult<Game> {
+        let library = LevelLibrary::load(ctx)?;
+        let progress = Progress::load(ctx);
+        let bindings = KeyBindings::load(ctx);
+        let settings = Settings::load(ctx);
+        let theme = Theme::load_or_default(ctx, &settings.theme);
+
+        // 存档只恢复一次，之后由下一次关闭窗口重新写入
+        let saved = SavedGame::load(ctx)
+            .and_then(|saved| Some((library.find(&saved.level)?, saved)));
+        SavedGame::delete(ctx);
+
         let mut world = World::new();
-        initialize_level(&mut world, ctx, 0);
+        let (current_level, ruleset) = match saved {
+            Some((current_level, saved)) => {
+                initialize_level(&mut world, ctx, &library, current_level, saved.ruleset, &theme);
+                if !saved.restore(&world) {
+                    println!("savegame does not match level {}, starting fresh", saved.level);
+                    world.clear();
+                    initialize_level(&mut world, ctx, &library, current_level, saved.ruleset, &theme);
+                }
+                (current_level, saved.ruleset)
+            }
+            None => {
+                let current_level = library.find(&progress.current_level).unwrap_or(0);
+                let ruleset = Ruleset::default();
+                initialize_level(&mut world, ctx, &library, current_level, ruleset, &theme);
+                (current_level, ruleset)
+            }
+        };
+
         Ok(Game {
             world,
-            current_level: 0,
+            library,
+            current_level,
+            ruleset,
+            progress,
+            bindings,
+            settings,
+            theme,
+            last_state: GameplayState::Playing,
+            imported: false,
         })
     }
+
+    /// 保存未完成的关卡，关卡没有开始、已经通关、正在回放或局面来自导入或回放的解法时删除旧存档
+    fn save_game(&self, ctx: &ggez::Context) {
+        let saved = if is_replaying(&self.world) || self.imported {
+            None
+        } else {
+            SavedGame::capture(&self.world, self.library.level_key(self.current_level), self.ruleset)
+        };
+        match saved {
+            Some(saved) => {
+                if let Err(error) = saved.save(ctx) {
+                    println!("failed to save game: {}", error);
+                }
+            }
+            None => SavedGame::delete(ctx),
+        }
+    }
+
+    /// 加载指定关卡（也用于重新开始当前关卡），并在存档中记住当前关卡
+    fn load_level(&mut self, ctx: &mut ggez::Context, level_index: usize) {
+        self.current_level = level_index;
+        self.world.clear();
+        initialize_level(&mut self.world, ctx, &self.library, self.current_level, self.ruleset, &self.theme);
+        self.last_state = GameplayState::Playing;
+        self.imported = false;
+
+        let level_key = self.library.level_key(self.current_level);
+        if self.progress.current_level != level_key {
+            self.progress.current_level = level_key;
+            self.save_progress(ctx);
+        }
+    }
+
+    /// 写入存档，失败时只打印错误，不影响游戏
+    fn save_progress(&self, ctx: &ggez::Context) {
+        if let Err(error) = self.progress.save(ctx) {
+            println!("failed to save progress: {}", error);
+        }
+    }
+
+    /// 通关时更新本关的最佳记录
+    fn record_win(&mut self, ctx: &ggez::Context) {
+        let score = {
+            let mut query = self.world.query::<&GamePlay>();
+            Score::from_gameplay(query.iter().next().unwrap().1)
+        };
+
+        let level_key = self.library.level_key(self.current_level);
+        if self.progress.record(&level_key, score) {
+            self.set_status("New record!".to_string());
+        }
+        self.save_progress(ctx);
+    }
+
+    /// 设置右侧面板的状态消息
+    fn set_status(&self, message: String) {
+        let mut query = self.world.query::<&mut SolverStatus>();
+        if let Some((_, status)) = query.iter().next() {
+            status.message = message;
+        }
+    }
+
+    /// 把本关的移动记录导出为解法文件
+    fn export_solution(&self, ctx: &ggez::Context) {
+        let move_log = {
+            let mut query = self.world.query::<&GamePlay>();
+            query.iter().next().map(|(_, gameplay)| gameplay.move_log.clone()).unwrap_or_default()
+        };
+        let message = match export_solution(ctx, &self.library, self.current_level, &move_log) {
+            Ok(path) => {
+                println!("solution exported to {}", path.display());
+                "Solution exported".to_string()
+            }
+            Err(error) => format!("Export: {}", error),
+        };
+        self.set_status(message);
+    }
+
+    /// 从解法文件导入 LURD 记录，在重新开始的当前关卡上执行
+    /// 导入的解法不计入成绩，直到重新加载或重新开始关卡
+    fn import_solution(&mut self, ctx: &mut ggez::Context) {
+        let moves = match import_solution(ctx, &self.library, self.current_level) {
+            Ok(moves) => moves,
+            Err(error) => {
+                self.set_status(format!("Import: {}", error));
+                return;
+            }
+        };
+
+        self.load_level(ctx, self.current_level);
+        self.imported = true;
+        let message = match apply_solution(&self.world, &moves) {
+            Ok(()) => format!("Imported {} moves", moves.len()),
+            Err(error) => format!("Import: {}", error),
+        };
+        self.set_status(message);
+    }
+
+    /// 打开设置菜单，高亮当前使用的主题
+    fn open_settings(&mut self, ctx: &ggez::Context) {
+        let themes: Vec<(String, String)> = list_themes(ctx).into_iter().map(|theme| (theme.id, theme.name)).collect();
+        let selected = themes.iter().position(|(id, _)| *id == self.theme.id).unwrap_or(0);
+        create_settings_menu(&mut self.world, themes, selected);
+    }
+
+    /// 关闭设置菜单
+    fn close_settings(&mut self) {
+        let menu = self.world.query::<&SettingsMenu>().iter().next().map(|(entity, _)| entity);
+        if let Some(entity) = menu {
+            let _ = self.world.despawn(entity);
+        }
+    }
+
+    /// 切换视觉主题并记住选择，当前关卡的局面保持不变
+    fn apply_theme(&mut self, ctx: &mut ggez::Context, id: &str) {
+        self.theme = Theme::load_or_default(ctx, id);
+        load_images(&mut self.world, ctx, &self.theme);
+        self.set_status(format!("Theme: {}", self.theme.name));
+
+        if self.settings.theme != self.theme.id {
+            self.settings.theme = self.theme.id.clone();
+            if let Err(error) = self.settings.save(ctx) {
+                println!("failed to save settings: {}", error);
+            }
+        }
+    }
+
+    /// 窗口坐标对应的棋盘格子（不检查是否在地图内）
+    fn board_position(&self, x: f32, y: f32) -> Position {
+        let tile_size = tile_size(&Grid::from_world(&self.world));
+        Position {
+            x: (x / tile_size) as u8,
+            y: (y / tile_size) as u8,
+            z: 0,
+        }
+    }
+
+    /// 开始或停止回放
+    /// 开始时读取本关的解法文件，在重新开始的关卡上逐步播放；停止后可以从当前局面继续游戏，
+    /// 但和导入的解法一样，重新开始关卡之前通关不计入成绩，也不保存存档
+    fn toggle_replay(&mut self, ctx: &mut ggez::Context) {
+        let replay = self.world.query::<&Replay>().iter().next().map(|(entity, _)| entity);
+        if let Some(entity) = replay {
+            let _ = self.world.despawn(entity);
+            self.set_status("Replay stopped".to_string());
+            return;
+        }
+
+        let moves = match import_solution(ctx, &self.library, self.current_level) {
+            Ok(moves) => moves,
+            Err(error) => {
+                self.set_status(format!("Replay: {}", error));
+                return;
+            }
+        };
+
+        self.load_level(ctx, self.current_level);
+        self.imported = true;
+        create_replay(&mut self.world, moves);
+    }
 }
 
 /// 实现 ggez 的 EventHandler trait，处理游戏循环
 impl event::EventHandler<ggez::GameError> for Game {
     /// 更新游戏状态（每帧调用）
     fn update(&mut self, ctx: &mut ggez::Context) -> GameResult {
+        // 上一帧之后按下的手柄按钮在这一帧生效
+        self.bindings.begin_frame();
+
+        // 设置菜单打开时只处理菜单；按设置键（默认 F2、手柄 Start）打开菜单（回放时不响应）
+        if is_settings_open(&self.world) {
+            match run_settings_menu(&self.world, ctx, &self.bindings) {
+                MenuCommand::Stay => {}
+                MenuCommand::Close => self.close_settings(),
+                MenuCommand::ApplyTheme(id) => {
+                    self.close_settings();
+                    self.apply_theme(ctx, &id);
+                }
+            }
+            return Ok(());
+        }
+        if self.bindings.is_just_pressed(ctx, Action::Settings) && !is_replaying(&self.world) {
+            self.open_settings(ctx);
+            return Ok(());
+        }
+
+        // 按关卡列表键（默认 PageUp/PageDown、手柄肩键）切换到列表中的上一关或下一关
+        // 回放时 PageUp/PageDown 用于跳转，不切换关卡
+        if !is_replaying(&self.world) {
+            let level_count = self.library.len();
+            if self.bindings.is_just_pressed(ctx, Action::ListPrevious) {
+                self.load_level(ctx, (self.current_level + level_count - 1) % level_count);
+                return Ok(());
+            }
+            if self.bindings.is_just_pressed(ctx, Action::ListNext) {
+                self.load_level(ctx, (self.current_level + 1) % level_count);
+                return Ok(());
+            }
+        }
+
+        // 关卡地图有误时只显示错误信息，按下一关键跳到下一关（也可以在列表中选择其他关卡）
+        if self.world.query::<&MapError>().iter().next().is_some() {
+            if self.bindings.is_just_pressed(ctx, Action::NextLevel) {
+                let next_level = (self.current_level + 1) % self.library.len();
+                self.load_level(ctx, next_level);
+            }
+            return Ok(());
+        }
+
+        let replaying = is_replaying(&self.world);
+        let events = if replaying {
+            // 回放模式下按键只控制回放
+            run_replay(&self.world, ctx)
+        } else {
+            let mut events = {
+                // 运行撤销/重做系统，重做的一步和正常移动一样播放音效
+                run_history(&self.world, ctx, &self.bindings)
+            };
+            {
+                // 运行输入处理系统，把按键翻译为动作交给模拟核心
+                events.extend(run_input(&self.world, ctx, &self.bindings));
+            }
+            {
+                // 运行点击移动系统，沿点击时计算的路径逐步行走
+                events.extend(run_click_move(&self.world, ctx));
+            }
+            {
+                // 运行提示系统
+                run_hint(&self.world, ctx, &self.bindings);
+            }
+            events
+        };
+        {
+            // 根据本帧产生的事件播放音效
+            play_sounds(&self.world, ctx, &events);
+        }
         {
-            // 运行输入处理系统
-            run_input(&self.world, ctx);
+            // 检查后台搜索（提示、求解）是否完成
+            run_search(&self.world);
         }
         {
             // 运行游戏逻辑系统（移动、状态检查等）
             run_gameplay_state(&self.world);
         }
+        {
+            // 刚刚通关时更新最佳记录（回放和导入的解法不计入成绩）
+            let state = {
+                let mut query = self.world.query::<&GamePlay>();
+                query.iter().next().map(|(_, gameplay)| gameplay.state).unwrap_or_default()
+            };
+            if state == GameplayState::Won && self.last_state != GameplayState::Won && !replaying && !self.imported {
+                self.record_win(ctx);
+            }
+            self.last_state = state;
+        }
+        {
+            // 按 F5 在后台运行求解器，结果显示在右侧面板
+            if ctx.keyboard.is_key_just_pressed(KeyCode::F5) {
+                start_search(&self.world, SearchPurpose::Solve);
+            }
+        }
+        {
+            // 按 I 导入本关的解法文件并在棋盘上重放
+            if ctx.keyboard.is_key_just_pressed(KeyCode::I) {
+                self.import_solution(ctx);
+            }
+            // 按 P 开始或停止回放本关的解法文件
+            if ctx.keyboard.is_key_just_pressed(KeyCode::P) {
+                self.toggle_replay(ctx);
+            }
+        }
         {
             // 更新时间组件
             let mut query = self.world.query::<&mut Time>();
@@ -48,40 +358,50 @@ impl event::EventHandler<ggez::GameError> for Game {
         // 处理全局状态转换（下一关/重试）
         // 这里需要获取 GamePlay 状态，但因为 run_gameplay_state 可能刚修改了它
         let mut next_action = None; // 0: nothing, 1: next level, 2: restart
+        let mut export_requested = false;
+
+        // 按 F6 切换全局默认规则，并重新开始当前关卡
+        if ctx.keyboard.is_key_just_pressed(KeyCode::F6) {
+            self.ruleset = self.ruleset.toggled();
+            next_action = Some(2);
+        }
 
         {
             let mut query = self.world.query::<&GamePlay>();
             if let Some(gameplay) = query.iter().next().map(|(_, g)| g) {
-                // 如果游戏胜利，按回车键进入下一关
+                // 如果游戏胜利，按下一关键（默认回车）进入下一关
                 if gameplay.state == GameplayState::Won {
-                    if ctx.keyboard.is_key_just_pressed(KeyCode::Return) {
+                    if self.bindings.is_just_pressed(ctx, Action::NextLevel) {
                         next_action = Some(1);
                     }
-                // 如果游戏失败（死锁），按 R 键重试
+                    // 按 E 把本关的移动记录导出为解法文件
+                    if ctx.keyboard.is_key_just_pressed(KeyCode::E) {
+                        export_requested = true;
+                    }
+                // 如果游戏失败（死锁），按重新开始键（默认 R）重试
                 } else if gameplay.state == GameplayState::Lost {
-                    if ctx.keyboard.is_key_just_pressed(KeyCode::R) {
+                    if self.bindings.is_just_pressed(ctx, Action::Restart) {
                         next_action = Some(2);
                     }
                 }
             }
         }
 
+        if export_requested {
+            self.export_solution(ctx);
+        }
+
         // 执行状态转换操作
         if let Some(action) = next_action {
             match action {
                 1 => {
-                    // 进入下一关
-                    self.current_level += 1;
-                    if self.current_level >= LEVELS.len() {
-                        self.current_level = 0; // 循环回到第一关
-                    }
-                    self.world.clear();
-                    initialize_level(&mut self.world, ctx, self.current_level);
+                    // 进入下一关，最后一关之后循环回到第一关
+                    let next_level = (self.current_level + 1) % self.library.len();
+                    self.load_level(ctx, next_level);
                 }
                 2 => {
                     // 重新开始当前关卡
-                    self.world.clear();
-                    initialize_level(&mut self.world, ctx, self.current_level);
+                    self.load_level(ctx, self.current_level);
                 }
                 _ => {}
             }
@@ -94,7 +414,45 @@ impl event::EventHandler<ggez::GameError> for Game {
     fn draw(&mut self, ctx: &mut ggez::Context) -> GameResult {
         {
             // 运行渲染系统
-            run_rendering(&self.world, ctx, self.current_level);
+            run_rendering(&self.world, ctx, &self.library, self.current_level, &self.progress, &self.bindings);
+        }
+        Ok(())
+    }
+
+    /// 关闭窗口时保存未完成的关卡，下次启动时恢复
+    fn quit_event(&mut self, ctx: &mut ggez::Context) -> GameResult<bool> {
+        self.save_game(ctx);
+        Ok(false)
+    }
+
+    /// 处理手柄按钮按下事件，按钮通过按键绑定翻译为动作，在下一次 update 中处理
+    fn gamepad_button_down_event(&mut self, _ctx: &mut ggez::Context, btn: Button, _id: GamepadId) -> GameResult {
+        self.bindings.press_button(btn);
+        Ok(())
+    }
+
+    /// 处理手柄按钮松开事件
+    fn gamepad_button_up_event(&mut self, _ctx: &mut ggez::Context, btn: Button, _id: GamepadId) -> GameResult {
+        self.bindings.release_button(btn);
+        Ok(())
+    }
+
+    /// 处理手柄摇杆事件：左摇杆与十字键一样用于移动
+    fn gamepad_axis_event(&mut self, _ctx: &mut ggez::Context, axis: Axis, value: f32, _id: GamepadId) -> GameResult {
+        self.bindings.move_axis(axis, value);
+        Ok(())
+    }
+
+    /// 处理鼠标松开事件：把按住的箱子拖到松开处的格子
+    fn mouse_button_up_event(
+        &mut self,
+        _ctx: &mut ggez::Context,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) -> GameResult {
+        if button == MouseButton::Left && x < BOARD_WIDTH && y < BOARD_HEIGHT && !is_replaying(&self.world) && !is_settings_open(&self.world) {
+            release_tile(&self.world, self.board_position(x, y));
         }
         Ok(())
     }
@@ -103,10 +461,23 @@ impl event::EventHandler<ggez::GameError> for Game {
     fn mouse_button_down_event(
         &mut self,
         ctx: &mut ggez::Context,
-        _button: MouseButton,
+        button: MouseButton,
         x: f32,
         y: f32,
     ) -> GameResult {
+        // 设置菜单打开时不响应鼠标
+        if is_settings_open(&self.world) {
+            return Ok(());
+        }
+
+        // 左键点击棋盘：选中箱子，或者走到（把选中的箱子推到）该格子（回放时不响应）
+        if button == MouseButton::Left && x < BOARD_WIDTH && y < BOARD_HEIGHT {
+            if !is_replaying(&self.world) {
+                click_tile(&self.world, self.board_position(x, y));
+            }
+            return Ok(());
+        }
+
         // 检查是否点击了关卡列表区域
         // 渲染位置参考 rendering.rs:
         // Level Select: 525.0, 160.0
@@ -126,12 +497,13 @@ impl event::EventHandler<ggez::GameError> for Game {
         } else {
             0
         };
-        let start_index = if start_index + visible_count > LEVELS.len() {
-            LEVELS.len().saturating_sub(visible_count)
+        let level_count = self.library.len();
+        let start_index = if start_index + visible_count > level_count {
+            level_count.saturating_sub(visible_count)
         } else {
             start_index
         };
-        let end_index = std::cmp::min(start_index + visible_count, LEVELS.len());
+        let end_index = std::cmp::min(start_index + visible_count, level_count);
 
         for i in start_index..end_index {
             let display_index = i - start_index;
@@ -140,9 +512,7 @@ impl event::EventHandler<ggez::GameError> for Game {
             if x >= start_x && x <= end_x && y >= item_y && y <= item_y + text_height {
                 // 点击了第 i 关
                 if self.current_level != i {
-                    self.current_level = i;
-                    self.world.clear();
-                    initialize_level(&mut self.world, ctx, self.current_level);
+                    self.load_level(ctx, i);
                 }
                 break;
             }
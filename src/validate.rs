@@ -0,0 +1,224 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use hecs::World;
+
+use crate::{component::{Box, BoxColor, BoxSpot, Player, Position, Wall}, grid::{Direction, Grid}};
+
+/// 关卡校验发现的问题
+#[derive(PartialEq, Eq)]
+pub enum LevelProblem {
+    /// 关卡中没有玩家
+    NoPlayer,
+    /// 关卡中有多个玩家，`position` 为第二个玩家的位置
+    MultiplePlayers { count: usize, position: Position },
+    /// 某种颜色的箱子数量与目标点数量不同
+    BoxGoalMismatch { color: BoxColor, boxes: usize, goals: usize },
+    /// 玩家可以走到地图边缘或墙外的空白处
+    NotEnclosed { position: Position },
+    /// 玩家走不到箱子所在的格子
+    UnreachableBox { position: Position },
+}
+
+impl Display for LevelProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LevelProblem::NoPlayer => write!(f, "no player"),
+            LevelProblem::MultiplePlayers { count, position } => {
+                write!(f, "{} players, expected exactly one (second player at {})", count, describe_position(position))
+            }
+            LevelProblem::BoxGoalMismatch { color, boxes, goals } => {
+                write!(f, "{} {} boxes but {} {} goals", boxes, color, goals, color)
+            }
+            LevelProblem::NotEnclosed { position } => {
+                write!(f, "not enclosed by walls at {}", describe_position(position))
+            }
+            LevelProblem::UnreachableBox { position } => {
+                write!(f, "box at {} is unreachable", describe_position(position))
+            }
+        }
+    }
+}
+
+/// 以从 1 开始的行号和列号描述格子位置
+pub fn describe_position(position: &Position) -> String {
+    format!("row {}, column {}", position.y as usize + 1, position.x as usize + 1)
+}
+
+/// 校验已加载到世界中的关卡，返回发现的所有问题（没有问题时为空）
+/// 检查玩家数量、每种颜色的箱子与目标点数量、地图是否被墙围住以及箱子是否可达
+pub fn validate_world(world: &World) -> Vec<LevelProblem> {
+    let mut problems = Vec::new();
+    let grid = Grid::from_world(world);
+
+    let players: Vec<Position> = world
+        .query::<(&Position, &Player)>()
+        .iter()
+        .map(|(_, (position, _))| *position)
+        .collect();
+    match players.len() {
+        0 => problems.push(LevelProblem::NoPlayer),
+        1 => {}
+        count => problems.push(LevelProblem::MultiplePlayers { count, position: players[1] }),
+    }
+
+    // 按颜色统计箱子和目标点数量
+    let mut counts: BTreeMap<BoxColor, (usize, usize)> = BTreeMap::new();
+    for (_, the_box) in world.query::<&Box>().iter() {
+        counts.entry(the_box.color).or_default().0 += 1;
+    }
+    for (_, box_spot) in world.query::<&BoxSpot>().iter() {
+        counts.entry(box_spot.color).or_default().1 += 1;
+    }
+    for (color, (boxes, goals)) in counts {
+        if boxes != goals {
+            problems.push(LevelProblem::BoxGoalMismatch { color, boxes, goals });
+        }
+    }
+
+    // 只有一个玩家时才能判断哪些区域可达
+    if let [player] = players.as_slice() {
+        let reachable = reachable_cells(world, &grid, player, &mut problems);
+        for (_, (position, _)) in world.query::<(&Position, &Box)>().iter() {
+            if !grid.index(position).is_some_and(|cell| reachable[cell]) {
+                problems.push(LevelProblem::UnreachableBox { position: *position });
+            }
+        }
+    }
+
+    problems
+}
+
+/// 从玩家位置出发做洪水填充（忽略箱子，不穿墙），返回每个格子是否可达
+/// 走到地图边缘或空白格子说明地图没有被墙围住，只记录第一处缺口
+fn reachable_cells(world: &World, grid: &Grid, player: &Position, problems: &mut Vec<LevelProblem>) -> Vec<bool> {
+    let mut walls = vec![false; grid.cell_count()];
+    // 除了空白（内部格式的 N、XSB 中墙外的空格），每个格子都有地图实体
+    let mut occupied = vec![false; grid.cell_count()];
+    for (_, position) in world.query::<&Position>().iter() {
+        if let Some(cell) = grid.index(position) {
+            occupied[cell] = true;
+        }
+    }
+    for (_, (position, _)) in world.query::<(&Position, &Wall)>().iter() {
+        if let Some(cell) = grid.index(position) {
+            walls[cell] = true;
+        }
+    }
+
+    let mut reachable = vec![false; grid.cell_count()];
+    let mut leak = None;
+    let mut stack: Vec<usize> = grid.index(player).into_iter().collect();
+    while let Some(cell) = stack.pop() {
+        if reachable[cell] || walls[cell] {
+            continue;
+        }
+        reachable[cell] = true;
+        if !occupied[cell] {
+            leak.get_or_insert(cell);
+            continue;
+        }
+        for direction in Direction::ALL {
+            match grid.step(cell, direction) {
+                Some(next) => stack.push(next),
+                None => {
+                    leak.get_or_insert(cell);
+                }
+            }
+        }
+    }
+
+    if let Some(cell) = leak {
+        problems.push(LevelProblem::NotEnclosed { position: grid.position(cell) });
+    }
+    reachable
+}
+
+#[cfg(test)]
+mod tests {
+    use hecs::World;
+
+    use super::{validate_world, LevelProblem};
+    use crate::{
+        component::{BoxColor, Position},
+        map::{load_map, load_xsb_map},
+    };
+
+    /// 只解析不校验，返回校验发现的问题
+    fn problems(map: &str) -> Vec<LevelProblem> {
+        let mut world = World::new();
+        let parsed = if map.contains('#') {
+            load_xsb_map(&mut world, map.to_string())
+        } else {
+            load_map(&mut world, map.to_string())
+        };
+        assert!(parsed.is_ok(), "invalid test map");
+        validate_world(&world)
+    }
+
+    /// 网格中的格子（缺口的位置）
+    fn at(x: u8, y: u8) -> Position {
+        Position { x, y, z: 0 }
+    }
+
+    /// 玩家或箱子所在的位置（与 `entity::create_player` / `create_box` 的层级相同）
+    fn piece_at(x: u8, y: u8) -> Position {
+        Position { x, y, z: 10 }
+    }
+
+    fn describe(problems: &[LevelProblem]) -> Vec<String> {
+        problems.iter().map(|problem| problem.to_string()).collect()
+    }
+
+    #[test]
+    fn valid_level_has_no_problems() {
+        assert!(problems("#####\n#@$.#\n#####").is_empty());
+    }
+
+    #[test]
+    fn no_player() {
+        let found = problems("#####\n# $.#\n#####");
+        assert!(found == vec![LevelProblem::NoPlayer], "{:?}", describe(&found));
+    }
+
+    #[test]
+    fn multiple_players() {
+        let found = problems("W W W W W W\nW P P BB BS W\nW W W W W W");
+        assert!(found == vec![LevelProblem::MultiplePlayers { count: 2, position: piece_at(2, 1) }], "{:?}", describe(&found));
+    }
+
+    #[test]
+    fn box_goal_mismatch_per_color() {
+        let found = problems("W W W W W W\nW P BB RB RS W\nW W W W W W");
+        assert!(
+            found == vec![LevelProblem::BoxGoalMismatch { color: BoxColor::Blue, boxes: 1, goals: 0 }],
+            "{:?}",
+            describe(&found)
+        );
+    }
+
+    #[test]
+    fn not_enclosed_at_the_map_edge_or_a_gap() {
+        // 地图右边缘没有墙
+        let found = problems("W W W W W\nW P BB BS .\nW W W W W");
+        assert!(found == vec![LevelProblem::NotEnclosed { position: at(4, 1) }], "{:?}", describe(&found));
+
+        // 墙上有一处空白
+        let found = problems("W W N W W\nW P BB BS W\nW W W W W");
+        assert!(found == vec![LevelProblem::NotEnclosed { position: at(2, 0) }], "{:?}", describe(&found));
+    }
+
+    #[test]
+    fn unreachable_box() {
+        let found = problems("W W W W W W\nW P W BB BS W\nW W W W W W");
+        assert!(found == vec![LevelProblem::UnreachableBox { position: piece_at(3, 1) }], "{:?}", describe(&found));
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        // 两种颜色的箱子都没有目标点，下方有缺口，红箱子在墙后
+        let found = problems("W W W W W W\nW P BB . W RB\nW W W N W W");
+        assert_eq!(found.len(), 4, "{:?}", describe(&found));
+        assert!(found.contains(&LevelProblem::NotEnclosed { position: at(3, 2) }), "{:?}", describe(&found));
+        assert!(found.contains(&LevelProblem::UnreachableBox { position: piece_at(5, 1) }), "{:?}", describe(&found));
+    }
+}